@@ -0,0 +1,79 @@
+use maud::{html, Markup, Render};
+
+use crate::{events::Action, i18n::Strings, state::State};
+
+/// The page's `aria-live` region, so screen readers hear about changes htmx swaps in silently.
+/// Responses to changes carry one out-of-band, saying what happened and how many todos are left.
+pub struct Announcement {
+    message: String,
+    oob: bool,
+}
+
+impl Announcement {
+    /// The region as the page first renders it, with nothing to say yet.
+    pub fn region() -> Self {
+        Announcement {
+            message: String::new(),
+            oob: false,
+        }
+    }
+
+    /// Announces `action` having been taken on `count` todos, leaving `state`.
+    pub fn new(state: &State, action: Action, count: usize, strings: &Strings) -> Self {
+        let (subject, verb) = match count {
+            1 => (strings.todo.to_string(), strings.verb(action).one),
+            count => (
+                format!("{count} {}", strings.todos),
+                strings.verb(action).many,
+            ),
+        };
+        let num_active = state.num_active();
+        let left = if num_active == 1 {
+            strings.item_left
+        } else {
+            strings.items_left
+        };
+
+        Announcement {
+            message: format!("{subject} {verb}, {num_active} {left}"),
+            oob: true,
+        }
+    }
+}
+
+impl Render for Announcement {
+    fn render(&self) -> Markup {
+        html! {
+            div.visually-hidden #announcer role="status" aria-live="polite"
+                hx-swap-oob=[self.oob.then(|| "true")] { (self.message) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maud::Render;
+
+    use super::Announcement;
+    use crate::{events::Action, i18n::ENGLISH, state::State, todos::Todo};
+
+    #[test]
+    fn announces_the_change_and_what_is_left() {
+        let state = State {
+            todos: vec![Todo::new(1, "buy milk"), Todo::new(2, "walk dog")],
+            ..State::default()
+        };
+
+        let html = Announcement::new(&state, Action::Added, 1, &ENGLISH)
+            .render()
+            .into_string();
+        assert!(html.contains(r#"aria-live="polite""#));
+        assert!(html.contains(r#"hx-swap-oob="true""#));
+        assert!(html.contains("Todo added, 2 items left"));
+
+        let html = Announcement::new(&state, Action::Deleted, 3, &ENGLISH)
+            .render()
+            .into_string();
+        assert!(html.contains("3 todos deleted, 2 items left"));
+    }
+}
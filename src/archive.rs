@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use axum::{http::StatusCode, response::IntoResponse, Extension};
+use maud::{html, DOCTYPE};
+use tower_sessions::Session;
+
+use crate::{config::Config, i18n::Strings, state::State, todos::ReadOnlyList, STYLESHEET_HREF};
+
+/// The completed todos cleared from the list, oldest first.
+pub async fn archive(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+    let archived = match state.archive.len() {
+        1 => strings.archived.one,
+        _ => strings.archived.many,
+    };
+
+    Ok(html! { (DOCTYPE) html lang=(strings.lang) {
+        head {
+            meta charset="utf-8";
+            meta name="viewport" content="width=device-width, initial-scale=1.0";
+            link rel="stylesheet" href=(*STYLESHEET_HREF);
+            title { (strings.archive) " - TodoMVC: axum, htmx, and maud" }
+        }
+        body {
+            section.todoapp {
+                header.header { h1 { (strings.title) } }
+                main.main { (ReadOnlyList(&state.archive)) }
+                footer.footer { span.todo-count { strong { (state.archive.len()) } " " (archived) } }
+            }
+        }
+    } })
+}
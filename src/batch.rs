@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use axum::{extract::Query, http::StatusCode, response::IntoResponse, Extension, Json};
+use maud::html;
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+
+use crate::{
+    config::Config,
+    events::{Action, Events},
+    i18n::Strings,
+    state::State,
+    todos::{List, Todo},
+};
+
+#[derive(Deserialize)]
+pub struct SetCompleted {
+    id: u64,
+    completed: bool,
+}
+#[derive(Serialize)]
+pub struct ToggleBatch {
+    todos: Vec<Todo>,
+    unknown: Vec<u64>,
+}
+/// Sets each todo's completion to the given value outright, rather than toggling whatever the
+/// client last saw, so offline changes can be replayed safely.
+pub async fn toggle_batch(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(config): Extension<Arc<Config>>,
+    Json(changes): Json<Vec<SetCompleted>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let mut updated = Vec::new();
+    let mut unknown = Vec::new();
+    let mut recorded = Vec::new();
+    for change in changes {
+        match state.todos.iter_mut().find(|todo| todo.id == change.id) {
+            Some(todo) => {
+                if todo.completed != change.completed {
+                    todo.set_completed(change.completed);
+                    todo.touch();
+                    recorded.push((change.completed, change.id));
+                }
+                updated.push(todo.clone());
+            }
+            None => unknown.push(change.id),
+        }
+    }
+    let recurred: Vec<u64> = recorded
+        .iter()
+        .filter(|(completed, _)| *completed)
+        .filter_map(|&(_, id)| state.recur(id))
+        .collect();
+    state.write(session.clone()).await?;
+    events.record(&session, Action::Added, recurred).await;
+    for (completed, id) in recorded {
+        let action = if completed {
+            Action::Completed
+        } else {
+            Action::Uncompleted
+        };
+        events.record(&session, action, [id]).await;
+    }
+
+    Ok(Json(ToggleBatch {
+        todos: updated,
+        unknown,
+    }))
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchOp {
+    Add { text: String },
+    Patch { id: u64, desc: String },
+    Delete { id: u64 },
+    Toggle { id: u64 },
+}
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum BatchResult {
+    Ok { id: u64 },
+    Err { error: String },
+}
+#[derive(Deserialize)]
+pub struct BatchQuery {
+    /// Applies either every operation or, when one fails, none of them.
+    #[serde(default)]
+    atomic: bool,
+}
+#[derive(Serialize)]
+pub struct Batch {
+    results: Vec<BatchResult>,
+    /// The list as it stands after the batch, as `POST /list` and friends render it.
+    list: String,
+}
+/// Applies a queue of changes in order with a single write. A failed operation is reported in
+/// its place in `results` and the rest still apply, unless `?atomic=true` asks for all or nothing,
+/// in which case nothing is saved and the response is 422 Unprocessable Entity.
+pub async fn batch(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(query): Query<BatchQuery>,
+    Json(ops): Json<Vec<BatchOp>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let mut results = Vec::new();
+    let mut recorded = Vec::new();
+    for op in ops {
+        match apply(&mut state, op, &config) {
+            Ok((action, id)) => {
+                recorded.push((action, id));
+                results.push(BatchResult::Ok { id });
+            }
+            Err(error) => {
+                results.push(BatchResult::Err { error });
+                if query.atomic {
+                    let state = State::read(session, &config).await?;
+                    let list = html! { (List::new(&state, &config, strings)) }.into_string();
+                    return Ok((
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        Json(Batch { results, list }),
+                    ));
+                }
+            }
+        }
+    }
+
+    state.write(session.clone()).await?;
+    for (action, id) in recorded {
+        events.record(&session, action, [id]).await;
+    }
+
+    let list = html! { (List::new(&state, &config, strings)) }.into_string();
+    Ok((StatusCode::OK, Json(Batch { results, list })))
+}
+
+pub fn apply(state: &mut State, op: BatchOp, config: &Config) -> Result<(Action, u64), String> {
+    let not_found = |id| format!("no todo with id {id}");
+    match op {
+        BatchOp::Add { text } => {
+            let text = Todo::sanitize_description(&text).map_err(|(_, err)| err)?;
+            state.check_capacity(config).map_err(|(_, err)| err)?;
+            Ok((Action::Added, state.add(&text).id))
+        }
+        BatchOp::Patch { id, desc } => {
+            let desc = Todo::sanitize_description(&desc).map_err(|(_, err)| err)?;
+            state
+                .set_description(id, &desc)
+                .ok_or_else(|| not_found(id))?;
+            Ok((Action::Edited, id))
+        }
+        BatchOp::Delete { id } => {
+            if !state.remove(id) {
+                return Err(not_found(id));
+            }
+            Ok((Action::Deleted, id))
+        }
+        BatchOp::Toggle { id } => {
+            let todo = state.toggle(id).ok_or_else(|| not_found(id))?;
+            let action = if todo.completed {
+                Action::Completed
+            } else {
+                Action::Uncompleted
+            };
+            Ok((action, id))
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    extract::{Path, Query},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension,
+};
+use chrono::{Datelike, Local, Months, NaiveDate, Weekday};
+use maud::{html, Markup, Render, DOCTYPE};
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    config::Config,
+    i18n::Strings,
+    state::State,
+    todos::{ReadOnlyList, Todo},
+    STYLESHEET_HREF,
+};
+
+/// A month of days, each with how many todos are due on it and a link to them. The arrows
+/// either side of the month's name swap in the month before or after.
+pub struct Calendar<'a> {
+    /// The first day of the month.
+    pub month: NaiveDate,
+    pub week_start: Weekday,
+    pub due: BTreeMap<NaiveDate, usize>,
+    pub strings: &'a Strings,
+}
+
+impl<'a> Calendar<'a> {
+    /// The month holding `day`, counting the todos of `state` due in it.
+    pub fn new(state: &State, day: NaiveDate, strings: &'a Strings) -> Self {
+        let month = day.with_day(1).unwrap_or(day);
+        let mut due = BTreeMap::new();
+        for date in state.todos.iter().filter_map(|todo| todo.due) {
+            if (date.year(), date.month()) == (month.year(), month.month()) {
+                *due.entry(date).or_insert(0) += 1;
+            }
+        }
+        Calendar {
+            month,
+            week_start: state.week_start,
+            due,
+            strings,
+        }
+    }
+
+    /// The days the grid shows, a week to a row, with `None` padding out the weeks the month
+    /// starts and ends in.
+    fn weeks(&self) -> Vec<Vec<Option<NaiveDate>>> {
+        let padding = (7 + self.month.weekday().num_days_from_monday()
+            - self.week_start.num_days_from_monday()) as usize
+            % 7;
+        let days = self
+            .month
+            .iter_days()
+            .take_while(|day| day.month() == self.month.month());
+        let mut cells: Vec<Option<NaiveDate>> = vec![None; padding];
+        cells.extend(days.map(Some));
+        cells.resize(cells.len().div_ceil(7) * 7, None);
+        cells.chunks(7).map(<[_]>::to_vec).collect()
+    }
+}
+
+/// Links to the calendar for `month`, swapping it in for the one shown.
+fn month_link(month: NaiveDate, label: &str, text: &str) -> Markup {
+    let href = format!("/calendar?month={}", month.format("%Y-%m"));
+    html! {
+        a href=(href) hx-get=(href) hx-target="#calendar" hx-swap="outerHTML" hx-push-url="true"
+            aria-label=(label) { (text) }
+    }
+}
+
+impl Render for Calendar<'_> {
+    fn render(&self) -> Markup {
+        let strings = self.strings;
+        let weekdays = (0..7).scan(self.week_start, |day, _| {
+            let current = *day;
+            *day = day.succ();
+            Some(current)
+        });
+
+        html! {
+            section.calendar #calendar {
+                header.calendar-month {
+                    @if let Some(previous) = self.month.checked_sub_months(Months::new(1)) {
+                        (month_link(previous, strings.previous_month, "‹"))
+                    }
+                    h2 { (strings.months[self.month.month0() as usize]) " " (self.month.year()) }
+                    @if let Some(next) = self.month.checked_add_months(Months::new(1)) {
+                        (month_link(next, strings.next_month, "›"))
+                    }
+                }
+                table {
+                    thead { tr {
+                        @for weekday in weekdays {
+                            th { (strings.weekdays[weekday.num_days_from_monday() as usize]) }
+                        }
+                    } }
+                    tbody {
+                        @for week in self.weeks() { tr {
+                            @for day in week { td {
+                                @if let Some(day) = day {
+                                    a href={ "/due/" (day) } {
+                                        span.day { (day.day()) }
+                                        @if let Some(count) = self.due.get(&day) {
+                                            " " span.due-count { (count) }
+                                        }
+                                    }
+                                }
+                            } }
+                        } }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CalendarQuery {
+    /// The month to show, as `YYYY-MM`. Defaults to the current one.
+    month: Option<String>,
+}
+/// The calendar of due dates. htmx requests, made by its arrows, get just the calendar to swap in.
+pub async fn calendar(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    Query(query): Query<CalendarQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let day = match query.month {
+        Some(month) => NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+            .map_err(|_| (StatusCode::BAD_REQUEST, format!("not a month: {month:?}")))?,
+        None => Local::now().date_naive(),
+    };
+    let state = State::read(session, &config).await?;
+    let calendar = Calendar::new(&state, day, strings);
+    if headers.contains_key("hx-request") {
+        return Ok(calendar.render());
+    }
+
+    Ok(html! { (DOCTYPE) html lang=(strings.lang) {
+        head {
+            meta charset="utf-8";
+            meta name="viewport" content="width=device-width, initial-scale=1.0";
+            script src="https://unpkg.com/htmx.org@1.9.11" integrity="sha384-0gxUXCCR8yv9FM2b+U3FDbsKthCI66oH5IA9fHppQq9DDMHuMauqq1ZHBpJxQ0J0" crossorigin="anonymous" { }
+            link rel="stylesheet" href=(*STYLESHEET_HREF);
+            title { (strings.calendar) " - TodoMVC: axum, htmx, and maud" }
+        }
+        body {
+            section.todoapp {
+                header.header { h1 { (strings.title) } }
+                main.main { (calendar) }
+                footer.footer { a href="/" { (strings.back_to_todos) } }
+            }
+        }
+    } })
+}
+
+/// The todos due on a day of the calendar.
+pub async fn due_on(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Path(day): Path<NaiveDate>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+    let due: Vec<Todo> = state
+        .todos
+        .iter()
+        .filter(|todo| todo.due == Some(day))
+        .cloned()
+        .collect();
+    let month = format!("/calendar?month={}", day.format("%Y-%m"));
+
+    Ok(html! { (DOCTYPE) html lang=(strings.lang) {
+        head {
+            meta charset="utf-8";
+            meta name="viewport" content="width=device-width, initial-scale=1.0";
+            link rel="stylesheet" href=(*STYLESHEET_HREF);
+            title { (strings.due) " " (day) " - TodoMVC: axum, htmx, and maud" }
+        }
+        body {
+            section.todoapp {
+                header.header { h1 { (strings.title) } }
+                main.main { (ReadOnlyList(&due)) }
+                footer.footer {
+                    span.todo-count { (strings.due) " " strong { (day) } }
+                    a href=(month) { (strings.calendar) }
+                }
+            }
+        }
+    } })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Weekday};
+    use maud::Render;
+
+    use super::Calendar;
+    use crate::{i18n::ENGLISH, state::State};
+
+    #[test]
+    fn the_month_starts_on_its_weekday() {
+        // May 2024 starts on a Wednesday.
+        let state = State {
+            week_start: Weekday::Mon,
+            ..State::default()
+        };
+        let day = NaiveDate::from_ymd_opt(2024, 5, 15).unwrap();
+        let html = Calendar::new(&state, day, &ENGLISH).render().into_string();
+
+        assert!(html.contains("<h2>May 2024</h2>"));
+        assert!(html.contains(r#"<tr><td></td><td></td><td><a href="/due/2024-05-01">"#));
+        assert!(html.contains(r#"href="/calendar?month=2024-04""#));
+        assert!(html.contains(r#"href="/calendar?month=2024-06""#));
+    }
+}
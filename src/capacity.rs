@@ -0,0 +1,36 @@
+use maud::{html, Markup, Render};
+
+use crate::{config::Config, i18n::Strings, state::State};
+
+/// Warns that the list is nearly full, before adding to it starts failing. The page always has
+/// the banner's region, and responses that change how many todos there are fill or empty it
+/// out-of-band.
+pub struct CapacityBanner<'a> {
+    pub near_capacity: bool,
+    pub max_todos: usize,
+    pub strings: &'a Strings,
+    pub oob: bool,
+}
+
+impl<'a> CapacityBanner<'a> {
+    pub fn new(state: &State, config: &Config, strings: &'a Strings) -> Self {
+        Self {
+            near_capacity: state.near_capacity(config),
+            max_todos: config.max_todos,
+            strings,
+            oob: true,
+        }
+    }
+}
+
+impl Render for CapacityBanner<'_> {
+    fn render(&self) -> Markup {
+        html! {
+            div.capacity-banner #capacity-banner role="alert" hx-swap-oob=[self.oob.then(|| "true")] {
+                @if self.near_capacity {
+                    (self.strings.near_the) (self.max_todos) (self.strings.todo_limit)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use std::{
+    env, fmt,
+    net::{Ipv4Addr, SocketAddr},
+    num::{NonZeroU32, NonZeroU64, NonZeroUsize},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use tower_sessions::cookie::SameSite;
+
+use crate::filter::Filter;
+
+/// What deleting a todo that has subtasks does with them.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtaskPolicy {
+    /// Asks which of the others to do, each time.
+    #[default]
+    Prompt,
+    /// Deletes them along with the todo.
+    Delete,
+    /// Keeps each as a todo of its own, where the todo was.
+    Promote,
+    /// Refuses to delete the todo while it has any.
+    Block,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub bind_address: SocketAddr,
+    /// The SurrealDB instance to keep sessions in, e.g. `ws://localhost:8000`. Sessions are kept
+    /// in memory when unset.
+    pub surreal_url: Option<String>,
+    pub surreal_user: Option<String>,
+    pub surreal_pass: Option<String>,
+    pub surreal_namespace: String,
+    pub surreal_database: String,
+    /// How many more times to try connecting to SurrealDB after the first attempt fails.
+    pub surreal_connect_retries: u32,
+    /// How long to wait before the first retry, in milliseconds. The wait doubles on each retry.
+    pub surreal_connect_backoff_ms: u64,
+    /// How many times a query that lost its connection is retried, reconnecting before each retry.
+    pub surreal_query_retries: u32,
+    /// How long to wait before the first reconnect, in milliseconds. The wait doubles each time.
+    pub surreal_query_backoff_ms: u64,
+    /// Whether the session cookie is only sent over HTTPS.
+    pub cookie_secure: bool,
+    pub cookie_same_site: SameSite,
+    /// Sessions expire after this many minutes without a request.
+    pub session_ttl_minutes: NonZeroU32,
+    /// The page warns that the session is about to expire this many seconds beforehand.
+    pub session_warning_secs: u32,
+    /// How often expired sessions are deleted from the store, in minutes.
+    pub session_cleanup_interval_minutes: NonZeroU64,
+    /// Descriptions longer than this many characters are truncated in the list.
+    pub description_render_length: usize,
+    /// How many minutes of estimated work can be done per day, used to project completion dates.
+    pub daily_capacity_minutes: NonZeroU32,
+    /// Requests with a body larger than this many bytes are answered with 413 Payload Too Large.
+    pub max_body_bytes: usize,
+    /// Requests still running after this many seconds are answered with 408 Request Timeout.
+    pub request_timeout_secs: u64,
+    /// Deleted todos can be restored for this many minutes before they are removed for good, by a
+    /// pass over every session run as often as expired sessions are deleted.
+    pub soft_delete_grace_minutes: u32,
+    /// The most todos a single session may hold.
+    pub max_todos: usize,
+    /// Lists holding at least this percentage of `max_todos` show a warning that they are nearly
+    /// full.
+    pub capacity_warning_percent: u8,
+    /// How many todos the list renders before offering to load more.
+    pub page_size: NonZeroUsize,
+    /// The filter tabs shown in the footer, in order.
+    pub filter_tabs: Vec<Filter>,
+    /// Whether the new-todo input is emptied after a todo is added, or keeps its text.
+    pub clear_new_todo: bool,
+    /// Whether adding a todo that matches an active one is refused with a warning, unless forced.
+    pub warn_duplicates: bool,
+    /// Whether completing a todo's last active subtask completes the todo too.
+    pub complete_with_subtasks: bool,
+    /// What deleting a todo that has subtasks does with them.
+    pub subtask_delete_policy: SubtaskPolicy,
+    /// How many mutating requests a client may make in a burst before being answered with 429.
+    pub rate_limit_burst: NonZeroU32,
+    /// How many requests a client regains from its burst every second.
+    pub rate_limit_per_second: NonZeroU32,
+    /// Unlocks the `/admin` routes, which are not served at all while it is unset.
+    pub admin_password: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind_address: SocketAddr::from((Ipv4Addr::LOCALHOST, 3000)),
+            surreal_url: None,
+            surreal_user: None,
+            surreal_pass: None,
+            surreal_namespace: "testing".to_string(),
+            surreal_database: "testing".to_string(),
+            surreal_connect_retries: 5,
+            surreal_connect_backoff_ms: 500,
+            surreal_query_retries: 3,
+            surreal_query_backoff_ms: 100,
+            cookie_secure: false,
+            cookie_same_site: SameSite::Strict,
+            session_ttl_minutes: NonZeroU32::new(30).unwrap(),
+            session_warning_secs: 120,
+            session_cleanup_interval_minutes: NonZeroU64::new(1).unwrap(),
+            description_render_length: 140,
+            daily_capacity_minutes: NonZeroU32::new(240).unwrap(),
+            max_body_bytes: 64 * 1024,
+            request_timeout_secs: 10,
+            soft_delete_grace_minutes: 60,
+            max_todos: 1000,
+            capacity_warning_percent: 90,
+            page_size: NonZeroUsize::new(50).unwrap(),
+            filter_tabs: vec![Filter::All, Filter::Active, Filter::Completed],
+            clear_new_todo: true,
+            warn_duplicates: true,
+            complete_with_subtasks: false,
+            subtask_delete_policy: SubtaskPolicy::default(),
+            rate_limit_burst: NonZeroU32::new(30).unwrap(),
+            rate_limit_per_second: NonZeroU32::new(5).unwrap(),
+            admin_password: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let default = Self::default();
+
+        let config = Self {
+            bind_address: var("BIND_ADDRESS", default.bind_address)?,
+            surreal_url: env::var("SURREAL_URL").ok(),
+            surreal_user: env::var("SURREAL_USER").ok(),
+            surreal_pass: env::var("SURREAL_PASS").ok(),
+            surreal_namespace: var("SURREAL_NAMESPACE", default.surreal_namespace)?,
+            surreal_database: var("SURREAL_DATABASE", default.surreal_database)?,
+            surreal_connect_retries: var(
+                "SURREAL_CONNECT_RETRIES",
+                default.surreal_connect_retries,
+            )?,
+            surreal_connect_backoff_ms: var(
+                "SURREAL_CONNECT_BACKOFF_MS",
+                default.surreal_connect_backoff_ms,
+            )?,
+            surreal_query_retries: var("SURREAL_QUERY_RETRIES", default.surreal_query_retries)?,
+            surreal_query_backoff_ms: var(
+                "SURREAL_QUERY_BACKOFF_MS",
+                default.surreal_query_backoff_ms,
+            )?,
+            cookie_secure: var("COOKIE_SECURE", default.cookie_secure)?,
+            cookie_same_site: parsed("COOKIE_SAMESITE", default.cookie_same_site, same_site)?,
+            session_ttl_minutes: var("SESSION_TTL_MINUTES", default.session_ttl_minutes)?,
+            session_warning_secs: var("SESSION_WARNING_SECS", default.session_warning_secs)?,
+            session_cleanup_interval_minutes: var(
+                "SESSION_CLEANUP_INTERVAL_MINUTES",
+                default.session_cleanup_interval_minutes,
+            )?,
+            description_render_length: var(
+                "DESCRIPTION_RENDER_LENGTH",
+                default.description_render_length,
+            )?,
+            daily_capacity_minutes: var("DAILY_CAPACITY_MINUTES", default.daily_capacity_minutes)?,
+            max_body_bytes: var("MAX_BODY_BYTES", default.max_body_bytes)?,
+            request_timeout_secs: var("REQUEST_TIMEOUT_SECS", default.request_timeout_secs)?,
+            soft_delete_grace_minutes: var(
+                "SOFT_DELETE_GRACE_MINUTES",
+                default.soft_delete_grace_minutes,
+            )?,
+            max_todos: var("MAX_TODOS", default.max_todos)?,
+            capacity_warning_percent: var(
+                "CAPACITY_WARNING_PERCENT",
+                default.capacity_warning_percent,
+            )?,
+            page_size: var("PAGE_SIZE", default.page_size)?,
+            filter_tabs: parsed("FILTER_TABS", default.filter_tabs, filter_tabs)?,
+            clear_new_todo: var("CLEAR_NEW_TODO", default.clear_new_todo)?,
+            warn_duplicates: var("WARN_DUPLICATES", default.warn_duplicates)?,
+            complete_with_subtasks: var("COMPLETE_WITH_SUBTASKS", default.complete_with_subtasks)?,
+            subtask_delete_policy: parsed(
+                "SUBTASK_DELETE_POLICY",
+                default.subtask_delete_policy,
+                subtask_policy,
+            )?,
+            rate_limit_burst: var("RATE_LIMIT_BURST", default.rate_limit_burst)?,
+            rate_limit_per_second: var("RATE_LIMIT_PER_SECOND", default.rate_limit_per_second)?,
+            admin_password: env::var("ADMIN_PASSWORD").ok(),
+        };
+
+        // Browsers drop SameSite=None cookies that aren't also Secure.
+        if config.cookie_same_site == SameSite::None && !config.cookie_secure {
+            return Err(ConfigError {
+                name: "COOKIE_SAMESITE",
+                value: env::var("COOKIE_SAMESITE").unwrap_or_default(),
+                reason: "requires COOKIE_SECURE=true".to_string(),
+            });
+        }
+
+        Ok(config)
+    }
+
+    /// The settings that are safe to show an operator. Credentials are left out, and so is the
+    /// SurrealDB URL, since it may carry them too.
+    pub(crate) fn public(&self) -> PublicConfig<'_> {
+        PublicConfig {
+            bind_address: self.bind_address,
+            surreal_remote: self.surreal_url.is_some(),
+            surreal_namespace: &self.surreal_namespace,
+            surreal_database: &self.surreal_database,
+            surreal_connect_retries: self.surreal_connect_retries,
+            surreal_connect_backoff_ms: self.surreal_connect_backoff_ms,
+            surreal_query_retries: self.surreal_query_retries,
+            surreal_query_backoff_ms: self.surreal_query_backoff_ms,
+            cookie_secure: self.cookie_secure,
+            cookie_same_site: self.cookie_same_site.to_string(),
+            session_ttl_minutes: self.session_ttl_minutes,
+            session_warning_secs: self.session_warning_secs,
+            session_cleanup_interval_minutes: self.session_cleanup_interval_minutes,
+            description_render_length: self.description_render_length,
+            daily_capacity_minutes: self.daily_capacity_minutes,
+            max_body_bytes: self.max_body_bytes,
+            request_timeout_secs: self.request_timeout_secs,
+            soft_delete_grace_minutes: self.soft_delete_grace_minutes,
+            max_todos: self.max_todos,
+            capacity_warning_percent: self.capacity_warning_percent,
+            page_size: self.page_size,
+            filter_tabs: &self.filter_tabs,
+            clear_new_todo: self.clear_new_todo,
+            warn_duplicates: self.warn_duplicates,
+            complete_with_subtasks: self.complete_with_subtasks,
+            subtask_delete_policy: self.subtask_delete_policy,
+            rate_limit_burst: self.rate_limit_burst,
+            rate_limit_per_second: self.rate_limit_per_second,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct PublicConfig<'a> {
+    bind_address: SocketAddr,
+    surreal_remote: bool,
+    surreal_namespace: &'a str,
+    surreal_database: &'a str,
+    surreal_connect_retries: u32,
+    surreal_connect_backoff_ms: u64,
+    surreal_query_retries: u32,
+    surreal_query_backoff_ms: u64,
+    cookie_secure: bool,
+    cookie_same_site: String,
+    session_ttl_minutes: NonZeroU32,
+    session_warning_secs: u32,
+    session_cleanup_interval_minutes: NonZeroU64,
+    description_render_length: usize,
+    daily_capacity_minutes: NonZeroU32,
+    max_body_bytes: usize,
+    request_timeout_secs: u64,
+    soft_delete_grace_minutes: u32,
+    max_todos: usize,
+    capacity_warning_percent: u8,
+    page_size: NonZeroUsize,
+    filter_tabs: &'a [Filter],
+    clear_new_todo: bool,
+    warn_duplicates: bool,
+    complete_with_subtasks: bool,
+    subtask_delete_policy: SubtaskPolicy,
+    rate_limit_burst: NonZeroU32,
+    rate_limit_per_second: NonZeroU32,
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    name: &'static str,
+    value: String,
+    reason: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {}={:?}: {}", self.name, self.value, self.reason)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn var<T>(name: &'static str, default: T) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    parsed(name, default, |value| {
+        value.parse().map_err(|err: T::Err| err.to_string())
+    })
+}
+
+fn parsed<T>(
+    name: &'static str,
+    default: T,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> Result<T, ConfigError> {
+    match env::var(name) {
+        Ok(value) => parse(&value).map_err(|reason| ConfigError {
+            name,
+            reason,
+            value,
+        }),
+        Err(_) => Ok(default),
+    }
+}
+
+fn same_site(value: &str) -> Result<SameSite, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "strict" => Ok(SameSite::Strict),
+        "lax" => Ok(SameSite::Lax),
+        "none" => Ok(SameSite::None),
+        _ => Err("expected one of \"strict\", \"lax\" or \"none\"".to_string()),
+    }
+}
+
+fn subtask_policy(value: &str) -> Result<SubtaskPolicy, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "prompt" => Ok(SubtaskPolicy::Prompt),
+        "delete" => Ok(SubtaskPolicy::Delete),
+        "promote" => Ok(SubtaskPolicy::Promote),
+        "block" => Ok(SubtaskPolicy::Block),
+        _ => Err("expected one of \"prompt\", \"delete\", \"promote\" or \"block\"".to_string()),
+    }
+}
+
+/// A comma separated list of filter names, each at most once.
+fn filter_tabs(value: &str) -> Result<Vec<Filter>, String> {
+    let mut tabs = Vec::new();
+    for name in value.split(',').map(str::trim) {
+        let filter = name.parse::<Filter>().map_err(|err| err.to_string())?;
+        if tabs.contains(&filter) {
+            return Err(format!("{filter} is listed more than once"));
+        }
+        tabs.push(filter);
+    }
+    Ok(tabs)
+}
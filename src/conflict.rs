@@ -0,0 +1,131 @@
+use maud::{html, Markup, Render};
+
+use crate::{i18n::Strings, todos::Todo};
+
+/// How one todo differs between two versions of a list.
+#[derive(Debug)]
+pub enum Change<'a> {
+    Unchanged(&'a Todo),
+    Added(&'a Todo),
+    Removed(&'a Todo),
+    /// The todo before the change, then after it.
+    Changed(&'a Todo, &'a Todo),
+}
+
+/// Pairs up the todos of `stale` and `current` by id, in the current order, followed by the todos
+/// only `stale` has.
+pub fn diff<'a>(stale: &'a [Todo], current: &'a [Todo]) -> Vec<Change<'a>> {
+    let mut changes: Vec<Change> = current
+        .iter()
+        .map(
+            |after| match stale.iter().find(|before| before.id == after.id) {
+                Some(before) if before.version == after.version => Change::Unchanged(after),
+                Some(before) => Change::Changed(before, after),
+                None => Change::Added(after),
+            },
+        )
+        .collect();
+    changes.extend(
+        stale
+            .iter()
+            .filter(|before| !current.iter().any(|after| after.id == before.id))
+            .map(Change::Removed),
+    );
+    changes
+}
+
+/// The answer to a change made against a todo that has moved on: the list as the page was
+/// rendered from beside the list as it is now, so the user can decide what to do. Swapped into
+/// the page's `#conflict` region.
+pub struct Conflict<'a> {
+    pub stale: &'a [Todo],
+    pub current: &'a [Todo],
+    pub strings: &'a Strings,
+}
+
+impl Conflict<'_> {
+    /// An `htmx:beforeSwap` handler swapping conflicts into their region, where htmx would
+    /// otherwise drop a 409 response.
+    pub const SHOW: &'static str = "if ($event.detail.xhr.status === 409 && \
+        $event.detail.xhr.getResponseHeader('content-type')?.startsWith('text/html')) { \
+        $event.detail.shouldSwap = true; $event.detail.isError = false; \
+        $event.detail.target = document.getElementById('conflict') }";
+
+    /// The page's empty region a conflict is swapped into.
+    pub fn region() -> Markup {
+        html! { div #conflict { } }
+    }
+}
+
+fn cell(todo: Option<&Todo>) -> Markup {
+    let completed = todo.is_some_and(|todo| todo.completed);
+    html! { td class=[completed.then_some("completed")] {
+        @if let Some(todo) = todo { (todo.description) }
+    } }
+}
+
+impl Render for Conflict<'_> {
+    fn render(&self) -> Markup {
+        let strings = self.strings;
+        html! {
+            section.conflict #conflict role="alertdialog" aria-labelledby="conflict-title" {
+                h2 #conflict-title { (strings.changed_elsewhere) }
+                table {
+                    thead { tr { th { (strings.your_list) } th { (strings.current_list) } } }
+                    tbody {
+                        @for change in diff(self.stale, self.current) {
+                            @match change {
+                                Change::Unchanged(todo) => tr { (cell(Some(todo))) (cell(Some(todo))) },
+                                Change::Added(todo) => tr.added { (cell(None)) (cell(Some(todo))) },
+                                Change::Removed(todo) => tr.removed { (cell(Some(todo))) (cell(None)) },
+                                Change::Changed(before, after) => tr.changed { (cell(Some(before))) (cell(Some(after))) },
+                            }
+                        }
+                    }
+                }
+                a href="/" { (strings.reload) }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Change};
+    use crate::todos::Todo;
+
+    #[test]
+    fn pairs_todos_up_by_id() {
+        let kept = Todo::new(1, "kept");
+        let edited = Todo::new(2, "edited");
+        let removed = Todo::new(3, "removed");
+        let mut after = edited.clone();
+        after.edit_description("edited again");
+        after.touch();
+        let added = Todo::new(4, "added");
+        let stale = vec![kept.clone(), edited, removed];
+        let current = vec![kept, after, added];
+
+        let changes: Vec<_> = diff(&stale, &current)
+            .into_iter()
+            .map(|change| match change {
+                Change::Unchanged(todo) => ("unchanged", todo.id),
+                Change::Added(todo) => ("added", todo.id),
+                Change::Removed(todo) => ("removed", todo.id),
+                Change::Changed(before, after) => {
+                    assert_eq!(before.description, "edited");
+                    ("changed", after.id)
+                }
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("unchanged", 1),
+                ("changed", 2),
+                ("added", 4),
+                ("removed", 3)
+            ]
+        );
+    }
+}
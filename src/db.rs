@@ -0,0 +1,282 @@
+use std::{
+    fmt,
+    future::Future,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::http::StatusCode;
+use surrealdb::{
+    engine::any::{self, Any},
+    opt::auth::Root,
+    Surreal,
+};
+
+use crate::config::Config;
+
+/// Connects to the SurrealDB instance at `SURREAL_URL`, or to an in-memory one when it is unset.
+/// A failed attempt is retried with backoff as configured, so the database may still be starting.
+pub async fn connect(config: &Config) -> surrealdb::Result<Surreal<Any>> {
+    retry(
+        config.surreal_connect_retries,
+        Duration::from_millis(config.surreal_connect_backoff_ms),
+        || connect_once(config),
+    )
+    .await
+}
+
+async fn connect_once(config: &Config) -> surrealdb::Result<Surreal<Any>> {
+    let url = config.surreal_url.as_deref().unwrap_or("mem://");
+    let db = any::connect(url).await?;
+
+    if let (Some(username), Some(password)) = (&config.surreal_user, &config.surreal_pass) {
+        db.signin(Root { username, password }).await?;
+    }
+
+    db.use_ns(&config.surreal_namespace)
+        .use_db(&config.surreal_database)
+        .await?;
+
+    Ok(db)
+}
+
+/// A SurrealDB handle that reconnects when a query fails for want of a connection, so a database
+/// restarted under a long-running server doesn't fail every request until it is restarted too.
+#[derive(Clone, Debug)]
+pub struct Database {
+    db: Arc<RwLock<Surreal<Any>>>,
+    /// Where to reconnect to, and how persistently.
+    config: Arc<Config>,
+}
+
+impl Database {
+    pub fn new(db: Surreal<Any>, config: Arc<Config>) -> Self {
+        Self {
+            db: Arc::new(RwLock::new(db)),
+            config,
+        }
+    }
+
+    /// Runs `query` against the database. A query that fails with a [transient](is_transient)
+    /// error is retried after reconnecting, as many times as configured.
+    pub async fn run<T, F>(&self, mut query: impl FnMut(Surreal<Any>) -> F) -> Result<T, DbError>
+    where
+        F: Future<Output = surrealdb::Result<T>>,
+    {
+        retry_transient(
+            self.config.surreal_query_retries,
+            Duration::from_millis(self.config.surreal_query_backoff_ms),
+            is_transient,
+            || query(self.db.read().unwrap().clone()),
+            || self.reconnect(),
+        )
+        .await
+        .map_err(DbError::from)
+    }
+
+    /// Replaces the handle with a fresh connection. An in-memory database has no connection to
+    /// lose, and connecting again would only start an empty one, so it is kept.
+    async fn reconnect(&self) -> surrealdb::Result<()> {
+        if self.config.surreal_url.is_none() {
+            return Ok(());
+        }
+        let db = connect_once(&self.config).await?;
+        *self.db.write().unwrap() = db;
+        Ok(())
+    }
+}
+
+/// Whether `err` came from the client's side of the connection, which may go away by
+/// reconnecting, rather than from the database refusing the query.
+fn is_transient(err: &surrealdb::Error) -> bool {
+    matches!(err, surrealdb::Error::Api(_))
+}
+
+/// A failed query, telling apart failures worth trying again later from ones that will keep
+/// failing.
+#[derive(Debug)]
+pub enum DbError {
+    Transient(surrealdb::Error),
+    Permanent(surrealdb::Error),
+}
+
+impl From<surrealdb::Error> for DbError {
+    fn from(err: surrealdb::Error) -> Self {
+        if is_transient(&err) {
+            DbError::Transient(err)
+        } else {
+            DbError::Permanent(err)
+        }
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Transient(err) => write!(f, "the database is unavailable: {err}"),
+            DbError::Permanent(err) => write!(f, "the database refused the query: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+/// Transient failures are answered with 503 Service Unavailable, so clients know to try again.
+impl From<DbError> for (StatusCode, String) {
+    fn from(err: DbError) -> Self {
+        let status = match err {
+            DbError::Transient(_) => StatusCode::SERVICE_UNAVAILABLE,
+            DbError::Permanent(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, err.to_string())
+    }
+}
+
+/// Runs `attempt` until it succeeds, fails with an error `is_transient` rejects, or has failed
+/// `retries` more times. Before each retry it waits, starting at `backoff` and doubling, then
+/// calls `reconnect`; a failed reconnect counts as a failed attempt.
+async fn retry_transient<T, E, F, R>(
+    retries: u32,
+    mut backoff: Duration,
+    is_transient: impl Fn(&E) -> bool,
+    mut attempt: impl FnMut() -> F,
+    mut reconnect: impl FnMut() -> R,
+) -> Result<T, E>
+where
+    E: fmt::Display,
+    F: Future<Output = Result<T, E>>,
+    R: Future<Output = Result<(), E>>,
+{
+    let mut remaining = retries;
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(err) if remaining > 0 && is_transient(&err) => {
+                tracing::warn!(
+                    "Lost the connection to SurrealDB, reconnecting in {backoff:?}: {err}"
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                remaining -= 1;
+                if let Err(err) = reconnect().await {
+                    tracing::error!("Failed to reconnect to SurrealDB: {err}");
+                }
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Runs `attempt` until it succeeds or has failed `retries` more times, waiting `backoff` before
+/// the first retry and twice as long before each one after that.
+async fn retry<T, E, F>(
+    retries: u32,
+    mut backoff: Duration,
+    mut attempt: impl FnMut() -> F,
+) -> Result<T, E>
+where
+    E: fmt::Display,
+    F: Future<Output = Result<T, E>>,
+{
+    let mut remaining = retries;
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(err) if remaining > 0 => {
+                tracing::warn!("Failed to connect to SurrealDB, retrying in {backoff:?}: {err}");
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                remaining -= 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, time::Duration};
+
+    use super::{retry, retry_transient};
+
+    #[tokio::test]
+    async fn retry_outlasts_early_failures() {
+        let attempts = Cell::new(0);
+        let result = retry(3, Duration::from_millis(1), || {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move {
+                if attempt < 3 {
+                    Err("unreachable")
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result, Ok(3));
+    }
+
+    #[tokio::test]
+    async fn retry_gives_up_after_the_last_retry() {
+        let attempts = Cell::new(0);
+        let result: Result<(), _> = retry(2, Duration::from_millis(1), || {
+            attempts.set(attempts.get() + 1);
+            async { Err("unreachable") }
+        })
+        .await;
+
+        assert_eq!(result, Err("unreachable"));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn transient_failures_reconnect_and_retry() {
+        let connected = Cell::new(false);
+        let reconnects = Cell::new(0);
+        let result = retry_transient(
+            3,
+            Duration::from_millis(1),
+            |err: &&str| *err == "disconnected",
+            || {
+                let connected = connected.get();
+                async move {
+                    if connected {
+                        Ok("rows")
+                    } else {
+                        Err("disconnected")
+                    }
+                }
+            },
+            || {
+                reconnects.set(reconnects.get() + 1);
+                connected.set(true);
+                async { Ok(()) }
+            },
+        )
+        .await;
+
+        assert_eq!(result, Ok("rows"));
+        assert_eq!(reconnects.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn permanent_failures_are_not_retried() {
+        let attempts = Cell::new(0);
+        let result: Result<(), _> = retry_transient(
+            3,
+            Duration::from_millis(1),
+            |err: &&str| *err == "disconnected",
+            || {
+                attempts.set(attempts.get() + 1);
+                async { Err("syntax error") }
+            },
+            || async { Ok(()) },
+        )
+        .await;
+
+        assert_eq!(result, Err("syntax error"));
+        assert_eq!(attempts.get(), 1);
+    }
+}
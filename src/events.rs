@@ -0,0 +1,116 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+
+use crate::db::{Database, DbError};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Action {
+    Added,
+    Edited,
+    Completed,
+    Uncompleted,
+    Deleted,
+    Restored,
+    Archived,
+    Templated,
+}
+
+/// One entry in the append-only audit log.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Event {
+    pub action: Action,
+    pub todo_id: u64,
+    pub session_id: String,
+    pub at: DateTime<Utc>,
+}
+
+/// The audit log, kept in the `events` table next to the sessions.
+#[derive(Clone)]
+pub struct Events {
+    db: Database,
+}
+
+impl Events {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Logs `action` against each of `todo_ids`. Failures are reported but never fail the
+    /// request, since the user's change has already been made.
+    pub async fn record(
+        &self,
+        session: &Session,
+        action: Action,
+        todo_ids: impl IntoIterator<Item = u64>,
+    ) {
+        if let Err(err) = self.try_record(session, action, todo_ids).await {
+            tracing::error!("Failed to record {action:?} event: {err}");
+        }
+    }
+
+    async fn try_record(
+        &self,
+        session: &Session,
+        action: Action,
+        todo_ids: impl IntoIterator<Item = u64>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let at = Utc::now();
+        let session_id = session_id(session).await?;
+        let events: Vec<Event> = todo_ids
+            .into_iter()
+            .map(|todo_id| Event {
+                action,
+                todo_id,
+                session_id: session_id.clone(),
+                at,
+            })
+            .collect();
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        self.db
+            .run(|db| {
+                let events = events.clone();
+                async move {
+                    db.query("INSERT INTO events $events")
+                        .bind(("events", events))
+                        .await?
+                        .check()?;
+                    Ok(())
+                }
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Every event recorded for `session`, oldest first.
+    pub async fn for_session(&self, session: &Session) -> Result<Vec<Event>, DbError> {
+        let Some(session_id) = session.id() else {
+            return Ok(Vec::new());
+        };
+
+        let session_id = session_id.to_string();
+        self.db
+            .run(|db| {
+                let session_id = session_id.clone();
+                async move {
+                    db.query("SELECT * FROM events WHERE session_id = $session_id ORDER BY at")
+                        .bind(("session_id", session_id))
+                        .await?
+                        .take(0)
+                }
+            })
+            .await
+    }
+}
+
+/// A brand new session only gets an id once it is saved, which would otherwise happen after the
+/// handler has returned.
+async fn session_id(session: &Session) -> Result<String, tower_sessions::session::Error> {
+    if session.id().is_none() {
+        session.save().await?;
+    }
+    Ok(session.id().map(|id| id.to_string()).unwrap_or_default())
+}
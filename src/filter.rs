@@ -1,6 +1,10 @@
+use std::{fmt, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+use crate::todos::Todo;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Hash)]
 pub enum Filter {
     #[default]
     All,
@@ -8,12 +12,121 @@ pub enum Filter {
     Completed,
 }
 
-impl ToString for Filter {
-    fn to_string(&self) -> String {
-        ToString::to_string(match self {
+impl Filter {
+    /// Whether a list under this filter shows `todo`.
+    pub fn matches(&self, todo: &Todo) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Active => !todo.completed,
+            Filter::Completed => todo.completed,
+        }
+    }
+
+    /// The page that shows the list under this filter.
+    pub fn href(&self) -> &'static str {
+        match self {
+            Filter::All => "/",
+            Filter::Active => "/active",
+            Filter::Completed => "/completed",
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
             Filter::All => "All",
             Filter::Active => "Active",
             Filter::Completed => "Completed",
         })
     }
 }
+
+#[derive(Debug, PartialEq)]
+pub struct ParseFilterError(String);
+
+impl fmt::Display for ParseFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown filter {:?}, expected one of \"all\", \"active\" or \"completed\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseFilterError {}
+
+impl FromStr for Filter {
+    type Err = ParseFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "all" => Ok(Filter::All),
+            "active" => Ok(Filter::Active),
+            "completed" => Ok(Filter::Completed),
+            _ => Err(ParseFilterError(s.to_string())),
+        }
+    }
+}
+
+/// Whether a todo must carry every tag of a [`TagFilter`], or any one of them.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMode {
+    #[default]
+    And,
+    Or,
+}
+
+/// Narrows the list to the todos carrying its tags. Without any tags it lets every todo through.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct TagFilter {
+    pub tags: Vec<String>,
+    pub mode: TagMode,
+}
+
+impl TagFilter {
+    /// Whether a list under this filter shows `todo`.
+    pub fn matches(&self, todo: &Todo) -> bool {
+        let carries = |tag: &String| todo.tags.contains(tag);
+        match self.mode {
+            _ if self.tags.is_empty() => true,
+            TagMode::And => self.tags.iter().all(carries),
+            TagMode::Or => self.tags.iter().any(carries),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filter, TagFilter, TagMode};
+    use crate::todos::Todo;
+
+    #[test]
+    fn parsing_ignores_case() {
+        assert_eq!("all".parse(), Ok(Filter::All));
+        assert_eq!("Active".parse(), Ok(Filter::Active));
+        assert_eq!("COMPLETED".parse(), Ok(Filter::Completed));
+    }
+
+    #[test]
+    fn unknown_filters_name_the_choices() {
+        let err = "done".parse::<Filter>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"unknown filter "done", expected one of "all", "active" or "completed""#
+        );
+    }
+
+    #[test]
+    fn or_mode_lets_through_todos_with_any_tag() {
+        let filter = TagFilter {
+            tags: vec!["home".to_string(), "work".to_string()],
+            mode: TagMode::Or,
+        };
+        assert!(filter.matches(&Todo::new(1, "call #work")));
+        assert!(!filter.matches(&Todo::new(2, "walk dog")));
+        assert!(TagFilter::default().matches(&Todo::new(3, "walk dog")));
+    }
+}
@@ -1,46 +1,174 @@
 use maud::{html, Markup, Render};
-use serde::{Deserialize, Serialize};
 
-use crate::{filter::Filter, state::State};
+use crate::{config::Config, filter::Filter, i18n::Strings, state::State};
 
-#[derive(Debug, Default, Deserialize, Serialize)]
-pub struct Footer {
+#[derive(Debug)]
+pub struct Footer<'a> {
     pub current_filter: Filter,
     pub num_active: usize,
     pub num_completed: usize,
+    /// The filters to offer, in order.
+    pub filter_tabs: &'a [Filter],
+    pub strings: &'a Strings,
     pub oob: bool,
 }
 
-impl From<&State> for Footer {
-    fn from(state: &State) -> Self {
+impl<'a> Footer<'a> {
+    pub fn new(state: &State, config: &'a Config, strings: &'a Strings) -> Self {
         Self {
-            num_active: state.todos.iter().filter(|todo| !todo.completed).count(),
-            num_completed: state.todos.iter().filter(|todo| todo.completed).count(),
+            num_active: state.num_active(),
+            num_completed: state.num_completed(),
             current_filter: state.filter.clone(),
+            filter_tabs: &config.filter_tabs,
+            strings,
             oob: true,
         }
     }
+
+    fn filters(&self, tabs: &[Filter], strings: &Strings) -> Markup {
+        html! { ul.filters hx-include="next input" {
+            @for filter in tabs { li {
+                a.selected[&self.current_filter == filter] href=(filter.href()) hx-post=("/select") { (strings.filter(filter)) }
+                input type="hidden" name="filter" value=(filter);
+            } }
+        } }
+    }
 }
 
-impl Render for Footer {
+impl Render for Footer<'_> {
     fn render(&self) -> Markup {
+        let strings = self.strings;
         html! { footer.footer #footer hx-swap-oob=[self.oob.then(|| "true")]
             hx-target="footer.footer" hx-swap="outerHTML" {
             span.todo-count {
                 strong { (self.num_active) }
-                " item" @if self.num_active != 1 { "s" } " left"
+                " " @if self.num_active == 1 { (strings.item_left) } @else { (strings.items_left) }
             }
 
-            ul.filters hx-include="next input" {
-                @for filter in [Filter::All, Filter::Active, Filter::Completed] { li {
-                    a.selected[self.current_filter == filter] hx-post=("/select") { (filter.to_string()) }
-                    input type="hidden" name="filter" value=(filter.to_string());
-                } }
-            }
+            (self.filters(self.filter_tabs, strings))
+
+            button.focus type="button" hx-post="/focus" hx-target="#todo-list" { (strings.focus) }
 
             @if self.num_completed > 0 {
-                button.clear-completed hx-post="/clear-completed" { "Clear completed" }
+                button.clear-completed hx-post="/clear-completed" hx-target="#todo-list" { (strings.clear_completed) }
+                span.completed-count {
+                    (self.num_completed) " "
+                    @if self.num_completed == 1 { (strings.item_completed) } @else { (strings.items_completed) }
+                }
             }
         } }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use maud::Render;
+
+    use super::Footer;
+    use crate::{filter::Filter, i18n::ENGLISH};
+
+    fn footer() -> Footer<'static> {
+        Footer {
+            current_filter: Filter::All,
+            num_active: 0,
+            num_completed: 0,
+            filter_tabs: &[Filter::All, Filter::Active, Filter::Completed],
+            strings: &ENGLISH,
+            oob: false,
+        }
+    }
+
+    #[test]
+    fn counts_are_pluralized() {
+        let html = Footer {
+            num_active: 1,
+            num_completed: 2,
+            ..footer()
+        }
+        .render()
+        .into_string();
+
+        assert!(html.contains("<strong>1</strong> item left"));
+        assert!(html.contains("2 items completed"));
+        assert!(html.contains("Clear completed"));
+    }
+
+    #[test]
+    fn clear_completed_only_shows_with_completed_todos() {
+        let html = Footer {
+            num_active: 3,
+            ..footer()
+        }
+        .render()
+        .into_string();
+
+        assert!(html.contains("<strong>3</strong> items left"));
+        assert!(!html.contains("Clear completed"));
+    }
+
+    #[test]
+    fn current_filter_is_selected() {
+        let html = Footer {
+            current_filter: Filter::Active,
+            ..footer()
+        }
+        .render()
+        .into_string();
+
+        assert!(html.contains(r#"class="selected" href="/active" hx-post="/select">Active</a>"#));
+        assert!(!html.contains(r#"class="selected" href="/" hx-post="/select">All</a>"#));
+    }
+
+    #[test]
+    fn filter_tabs_follow_the_given_order() {
+        let html = footer()
+            .filters(&[Filter::Completed, Filter::All], &ENGLISH)
+            .into_string();
+
+        let completed = html.find(">Completed</a>").unwrap();
+        let all = html.find(">All</a>").unwrap();
+        assert!(completed < all);
+        assert!(!html.contains(">Active</a>"));
+    }
+
+    #[test]
+    fn filters_link_to_their_pages() {
+        let html = footer().render().into_string();
+
+        assert!(html.contains(r#"href="/" hx-post="/select">All</a>"#));
+        assert!(html.contains(r#"href="/active" hx-post="/select">Active</a>"#));
+        assert!(html.contains(r#"href="/completed" hx-post="/select">Completed</a>"#));
+    }
+
+    #[test]
+    fn swaps_out_of_band_only_when_asked() {
+        let oob = Footer {
+            oob: true,
+            ..footer()
+        }
+        .render()
+        .into_string();
+        assert!(oob.starts_with(r#"<footer class="footer" id="footer" hx-swap-oob="true""#));
+
+        let in_place = footer().render().into_string();
+        assert!(in_place.starts_with(r#"<footer class="footer" id="footer" hx-target"#));
+    }
+
+    #[test]
+    fn renders_the_same_under_each_filter() {
+        for filter in [Filter::All, Filter::Active, Filter::Completed] {
+            let footer = Footer {
+                current_filter: filter.clone(),
+                num_active: 1,
+                num_completed: 1,
+                filter_tabs: &[Filter::All, Filter::Active, Filter::Completed],
+                strings: &ENGLISH,
+                oob: true,
+            };
+            insta::assert_snapshot!(
+                format!("footer_{filter}").to_lowercase(),
+                footer.render().into_string()
+            );
+        }
+    }
+}
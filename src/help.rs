@@ -0,0 +1,135 @@
+use maud::{html, Markup, Render};
+
+use crate::i18n::Strings;
+
+/// How the help overlay lists a shortcut in one language.
+#[derive(Debug)]
+pub struct ShortcutLabel {
+    pub keys: &'static str,
+    pub action: &'static str,
+}
+
+/// A shortcut or gesture the page responds to. Every one is in [`SHORTCUTS`], which both the
+/// page's handlers and the help overlay are built from, so a new binding only goes there and in
+/// each language's labels.
+#[derive(Debug)]
+pub struct Shortcut {
+    /// The key as `KeyboardEvent.key` names it, or the DOM event for a gesture.
+    pub key: &'static str,
+    /// The Alpine statement the page-wide `keydown` handler runs on the key. Shortcuts without one
+    /// are bound by the elements they act on.
+    pub action: Option<&'static str>,
+    /// Whether the page-wide handler still runs the action while a text field has focus.
+    pub while_typing: bool,
+    pub label: fn(&Strings) -> &ShortcutLabel,
+}
+
+impl Shortcut {
+    /// An `hx-trigger` firing when the key is released.
+    pub fn trigger(&self) -> String {
+        format!("keyup[key=='{}']", self.key)
+    }
+}
+
+pub const SAVE: Shortcut = Shortcut {
+    key: "Enter",
+    action: None,
+    while_typing: true,
+    label: |strings| &strings.save_shortcut,
+};
+
+pub const EDIT: Shortcut = Shortcut {
+    key: "dblclick",
+    action: None,
+    while_typing: true,
+    label: |strings| &strings.edit_shortcut,
+};
+
+pub const TOGGLE_HELP: Shortcut = Shortcut {
+    key: "?",
+    action: Some("help = !help"),
+    while_typing: false,
+    label: |strings| &strings.toggle_help_shortcut,
+};
+
+pub const CLOSE_HELP: Shortcut = Shortcut {
+    key: "Escape",
+    action: Some("help = false"),
+    while_typing: true,
+    label: |strings| &strings.close_help_shortcut,
+};
+
+/// Every shortcut, in the order the help overlay lists them.
+pub const SHORTCUTS: &[Shortcut] = &[SAVE, EDIT, TOGGLE_HELP, CLOSE_HELP];
+
+/// The overlay listing [`SHORTCUTS`], shown while the page's Alpine `help` flag is set.
+pub struct Help<'a>(pub &'a Strings);
+
+impl Help<'_> {
+    /// Alpine bindings for the element that owns the `help` flag.
+    pub const DATA: &'static str = "{ help: false }";
+
+    /// The page-wide `keydown` handler, running each shortcut's action on its key.
+    pub fn keydown() -> String {
+        SHORTCUTS
+            .iter()
+            .filter_map(|shortcut| {
+                let action = shortcut.action?;
+                let typing = if shortcut.while_typing {
+                    ""
+                } else {
+                    " && !['INPUT', 'TEXTAREA'].includes($event.target.tagName)"
+                };
+                Some(format!(
+                    "if ($event.key === '{}'{typing}) {action}",
+                    shortcut.key
+                ))
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+impl Render for Help<'_> {
+    fn render(&self) -> Markup {
+        let strings = self.0;
+        html! {
+            div.help-overlay x-show="help" x-cloak "x-on:click.self"="help = false" {
+                section.help role="dialog" aria-labelledby="help-title" {
+                    h2 #help-title { (strings.keyboard_shortcuts) }
+                    dl {
+                        @for shortcut in SHORTCUTS {
+                            @let label = (shortcut.label)(strings);
+                            dt { kbd { (label.keys) } }
+                            dd { (label.action) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maud::Render;
+
+    use super::Help;
+    use crate::i18n::ENGLISH;
+
+    #[test]
+    fn lists_the_edit_gesture() {
+        let html = Help(&ENGLISH).render().into_string();
+
+        assert!(html.contains("<dt><kbd>Double-click</kbd></dt><dd>Edit a todo</dd>"));
+    }
+
+    #[test]
+    fn keydown_runs_each_page_wide_action() {
+        assert_eq!(
+            Help::keydown(),
+            "if ($event.key === '?' && !['INPUT', 'TEXTAREA'].includes($event.target.tagName)) \
+             help = !help; if ($event.key === 'Escape') help = false"
+        );
+    }
+}
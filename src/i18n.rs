@@ -0,0 +1,511 @@
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tower_sessions::cookie::Cookie;
+
+use crate::{events::Action, filter::Filter, help::ShortcutLabel};
+
+/// Overrides `Accept-Language` with one of the [`LANGUAGES`] by its code.
+pub const LANG_COOKIE: &str = "lang";
+
+/// How announcements describe an action taken on one todo, and on several.
+#[derive(Debug)]
+pub struct Verb {
+    pub one: &'static str,
+    pub many: &'static str,
+}
+
+/// How long ago something happened, in the largest whole unit: `before`, the number, the unit,
+/// then `after`.
+#[derive(Debug)]
+pub struct Elapsed {
+    pub just_now: &'static str,
+    pub before: &'static str,
+    pub minutes: &'static str,
+    pub hours: &'static str,
+    pub days: &'static str,
+    pub after: &'static str,
+}
+
+/// The text of the interface in one language.
+#[derive(Debug)]
+pub struct Strings {
+    /// The language's code, as used in `Accept-Language` and the page's `lang` attribute.
+    pub lang: &'static str,
+    pub title: &'static str,
+    /// Shown in the empty new-todo input, one picked at random on each page load.
+    pub placeholders: &'static [&'static str],
+    pub search: &'static str,
+    pub mark_all_complete: &'static str,
+    pub item_left: &'static str,
+    pub items_left: &'static str,
+    pub all: &'static str,
+    pub active: &'static str,
+    pub completed: &'static str,
+    pub clear_completed: &'static str,
+    pub item_completed: &'static str,
+    pub items_completed: &'static str,
+    pub edit_hint: &'static str,
+    pub focus: &'static str,
+    pub show: &'static str,
+    pub theme: &'static str,
+    pub system: &'static str,
+    pub light: &'static str,
+    pub dark: &'static str,
+    /// Announcements start with the todo, or the number of todos, a change touched.
+    pub todo: &'static str,
+    pub todos: &'static str,
+    pub added: Verb,
+    pub edited: Verb,
+    pub marked_completed: Verb,
+    pub marked_active: Verb,
+    pub deleted: Verb,
+    pub restored: Verb,
+    pub archived: Verb,
+    pub templated: Verb,
+    pub keyboard_shortcuts: &'static str,
+    /// Each of the [`SHORTCUTS`](crate::help::SHORTCUTS) as the help overlay lists it.
+    pub save_shortcut: ShortcutLabel,
+    pub edit_shortcut: ShortcutLabel,
+    pub toggle_help_shortcut: ShortcutLabel,
+    pub close_help_shortcut: ShortcutLabel,
+    /// Around the `?` key in the hint at the foot of the page.
+    pub press: &'static str,
+    pub for_keyboard_shortcuts: &'static str,
+    pub session_expiring: &'static str,
+    pub stay_signed_in: &'static str,
+    pub created_by: &'static str,
+    pub based_on: &'static str,
+    pub not_found: &'static str,
+    pub nothing_here: &'static str,
+    pub back_to_todos: &'static str,
+    pub show_more: &'static str,
+    pub load_more: &'static str,
+    pub tagged: &'static str,
+    /// Between [`tagged`](Self::tagged) and the tags, when the list is filtered by several.
+    pub all_of: &'static str,
+    pub any_of: &'static str,
+    pub show_all_tags: &'static str,
+    /// Around the most todos a list may hold, in the warning that it is nearly full.
+    pub near_the: &'static str,
+    pub todo_limit: &'static str,
+    /// Follows the quoted description of the todo a new one duplicates.
+    pub already_on_your_list: &'static str,
+    pub new_list: &'static str,
+    pub archive: &'static str,
+    pub snapshot: &'static str,
+    pub edit_todo_input: &'static str,
+    pub add_a_subtask: &'static str,
+    pub duplicate: &'static str,
+    pub subtasks_completed: &'static str,
+    pub due: &'static str,
+    pub done: &'static str,
+    pub elapsed: Elapsed,
+    pub calendar: &'static str,
+    /// The choices offered on deleting a todo that has subtasks.
+    pub delete_subtasks_prompt: &'static str,
+    pub delete_subtasks: &'static str,
+    pub promote_subtasks: &'static str,
+    pub cancel: &'static str,
+    pub delete_subtasks_first: &'static str,
+    /// January first.
+    pub months: [&'static str; 12],
+    /// Abbreviated, Monday first.
+    pub weekdays: [&'static str; 7],
+    pub previous_month: &'static str,
+    pub next_month: &'static str,
+    /// Heads the lists shown side by side when a change conflicts with one made elsewhere.
+    pub changed_elsewhere: &'static str,
+    pub your_list: &'static str,
+    pub current_list: &'static str,
+    pub reload: &'static str,
+}
+
+impl Strings {
+    pub fn filter(&self, filter: &Filter) -> &'static str {
+        match filter {
+            Filter::All => self.all,
+            Filter::Active => self.active,
+            Filter::Completed => self.completed,
+        }
+    }
+
+    pub fn verb(&self, action: Action) -> &Verb {
+        match action {
+            Action::Added => &self.added,
+            Action::Edited => &self.edited,
+            Action::Completed => &self.marked_completed,
+            Action::Uncompleted => &self.marked_active,
+            Action::Deleted => &self.deleted,
+            Action::Restored => &self.restored,
+            Action::Archived => &self.archived,
+            Action::Templated => &self.templated,
+        }
+    }
+
+    /// The language picked by the `lang` cookie, or failing that the first supported one in
+    /// `Accept-Language`, or failing that English.
+    pub fn negotiate(headers: &HeaderMap) -> &'static Strings {
+        let from_cookie = headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(Cookie::split_parse)
+            .filter_map(Result::ok)
+            .find(|cookie| cookie.name() == LANG_COOKIE)
+            .and_then(|cookie| Self::by_code(cookie.value()));
+        let from_header = || {
+            headers
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())?
+                .split(',')
+                .filter_map(|range| range.split(';').next())
+                .find_map(|tag| Self::by_code(tag.trim().split('-').next().unwrap_or_default()))
+        };
+
+        from_cookie.or_else(from_header).unwrap_or(&ENGLISH)
+    }
+
+    fn by_code(code: &str) -> Option<&'static Strings> {
+        LANGUAGES
+            .iter()
+            .copied()
+            .find(|strings| strings.lang.eq_ignore_ascii_case(code))
+    }
+}
+
+/// Hands the handlers the [`Strings`] of the language the request asked for, as an
+/// `Extension<&'static Strings>`.
+pub async fn localize(mut request: Request, next: Next) -> Response {
+    let strings = Strings::negotiate(request.headers());
+    request.extensions_mut().insert(strings);
+    let mut response = next.run(request).await;
+    response.headers_mut().append(
+        header::VARY,
+        HeaderValue::from_static("accept-language, cookie"),
+    );
+    response
+}
+
+pub static LANGUAGES: &[&Strings] = &[&ENGLISH, &SPANISH];
+
+pub static ENGLISH: Strings = Strings {
+    lang: "en",
+    title: "todos",
+    placeholders: &[
+        "What needs to be done?",
+        "Water the plants",
+        "Call mom back",
+        "Book a dentist appointment",
+        "Pick up the dry cleaning",
+        "Reply to that email",
+    ],
+    search: "Search",
+    mark_all_complete: "Mark all as complete",
+    item_left: "item left",
+    items_left: "items left",
+    all: "All",
+    active: "Active",
+    completed: "Completed",
+    clear_completed: "Clear completed",
+    item_completed: "item completed",
+    items_completed: "items completed",
+    edit_hint: "Double-click to edit a todo",
+    focus: "Focus",
+    show: "show",
+    theme: "Theme",
+    system: "System",
+    light: "Light",
+    dark: "Dark",
+    todo: "Todo",
+    todos: "todos",
+    added: Verb {
+        one: "added",
+        many: "added",
+    },
+    edited: Verb {
+        one: "edited",
+        many: "edited",
+    },
+    marked_completed: Verb {
+        one: "completed",
+        many: "completed",
+    },
+    marked_active: Verb {
+        one: "marked active",
+        many: "marked active",
+    },
+    deleted: Verb {
+        one: "deleted",
+        many: "deleted",
+    },
+    restored: Verb {
+        one: "restored",
+        many: "restored",
+    },
+    archived: Verb {
+        one: "archived",
+        many: "archived",
+    },
+    templated: Verb {
+        one: "made a template",
+        many: "made a template",
+    },
+    keyboard_shortcuts: "Keyboard shortcuts",
+    save_shortcut: ShortcutLabel {
+        keys: "Enter",
+        action: "Add the new todo, or save the todo being edited",
+    },
+    edit_shortcut: ShortcutLabel {
+        keys: "Double-click",
+        action: "Edit a todo",
+    },
+    toggle_help_shortcut: ShortcutLabel {
+        keys: "?",
+        action: "Show or hide this help",
+    },
+    close_help_shortcut: ShortcutLabel {
+        keys: "Escape",
+        action: "Close this help",
+    },
+    press: "Press",
+    for_keyboard_shortcuts: "for keyboard shortcuts",
+    session_expiring: "Your session expires soon because of inactivity.",
+    stay_signed_in: "Stay signed in",
+    created_by: "Created by",
+    based_on: "Based on",
+    not_found: "Not found",
+    nothing_here: "Nothing here.",
+    back_to_todos: "Back to your todos",
+    show_more: "show more",
+    load_more: "Load more",
+    tagged: "Tagged",
+    all_of: "with all of",
+    any_of: "with any of",
+    show_all_tags: "Show all tags",
+    near_the: "You're near the ",
+    todo_limit: "-todo limit",
+    already_on_your_list: "is already on your list.",
+    new_list: "New list",
+    archive: "Archive",
+    snapshot: "Snapshot",
+    edit_todo_input: "Edit Todo Input",
+    add_a_subtask: "Add a subtask",
+    duplicate: "Duplicate",
+    subtasks_completed: "Subtasks completed",
+    due: "due",
+    done: "done",
+    elapsed: Elapsed {
+        just_now: "just now",
+        before: "",
+        minutes: "m",
+        hours: "h",
+        days: "d",
+        after: " ago",
+    },
+    calendar: "Calendar",
+    delete_subtasks_prompt: "This todo has subtasks. What should happen to them?",
+    delete_subtasks: "Delete them too",
+    promote_subtasks: "Keep them as todos",
+    cancel: "Cancel",
+    delete_subtasks_first: "Delete its subtasks first",
+    months: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    previous_month: "Previous month",
+    next_month: "Next month",
+    changed_elsewhere: "This todo was changed elsewhere since you loaded the page.",
+    your_list: "Your list",
+    current_list: "Current list",
+    reload: "Reload",
+};
+
+pub static SPANISH: Strings = Strings {
+    lang: "es",
+    title: "tareas",
+    placeholders: &[
+        "¿Qué hay que hacer?",
+        "Regar las plantas",
+        "Llamar a mamá",
+        "Pedir cita con el dentista",
+        "Recoger la ropa de la tintorería",
+        "Responder ese correo",
+    ],
+    search: "Buscar",
+    mark_all_complete: "Marcar todas como completadas",
+    item_left: "tarea pendiente",
+    items_left: "tareas pendientes",
+    all: "Todas",
+    active: "Activas",
+    completed: "Completadas",
+    clear_completed: "Borrar completadas",
+    item_completed: "tarea completada",
+    items_completed: "tareas completadas",
+    edit_hint: "Haz doble clic para editar una tarea",
+    focus: "Concentrarse",
+    show: "mostrar",
+    theme: "Tema",
+    system: "Sistema",
+    light: "Claro",
+    dark: "Oscuro",
+    todo: "Tarea",
+    todos: "tareas",
+    added: Verb {
+        one: "añadida",
+        many: "añadidas",
+    },
+    edited: Verb {
+        one: "editada",
+        many: "editadas",
+    },
+    marked_completed: Verb {
+        one: "completada",
+        many: "completadas",
+    },
+    marked_active: Verb {
+        one: "marcada como activa",
+        many: "marcadas como activas",
+    },
+    deleted: Verb {
+        one: "eliminada",
+        many: "eliminadas",
+    },
+    restored: Verb {
+        one: "restaurada",
+        many: "restauradas",
+    },
+    archived: Verb {
+        one: "archivada",
+        many: "archivadas",
+    },
+    templated: Verb {
+        one: "convertida en plantilla",
+        many: "convertidas en plantillas",
+    },
+    keyboard_shortcuts: "Atajos de teclado",
+    save_shortcut: ShortcutLabel {
+        keys: "Intro",
+        action: "Añadir la nueva tarea, o guardar la tarea que se está editando",
+    },
+    edit_shortcut: ShortcutLabel {
+        keys: "Doble clic",
+        action: "Editar una tarea",
+    },
+    toggle_help_shortcut: ShortcutLabel {
+        keys: "?",
+        action: "Mostrar u ocultar esta ayuda",
+    },
+    close_help_shortcut: ShortcutLabel {
+        keys: "Escape",
+        action: "Cerrar esta ayuda",
+    },
+    press: "Pulsa",
+    for_keyboard_shortcuts: "para ver los atajos de teclado",
+    session_expiring: "Tu sesión caducará pronto por inactividad.",
+    stay_signed_in: "Mantener la sesión",
+    created_by: "Creado por",
+    based_on: "Basado en",
+    not_found: "No encontrado",
+    nothing_here: "Aquí no hay nada.",
+    back_to_todos: "Volver a tus tareas",
+    show_more: "mostrar más",
+    load_more: "Cargar más",
+    tagged: "Etiquetadas",
+    all_of: "con todas estas:",
+    any_of: "con alguna de estas:",
+    show_all_tags: "Mostrar todas las etiquetas",
+    near_the: "Estás cerca del límite de ",
+    todo_limit: " tareas",
+    already_on_your_list: "ya está en tu lista.",
+    new_list: "Nueva lista",
+    archive: "Archivo",
+    snapshot: "Instantánea",
+    edit_todo_input: "Editar la tarea",
+    add_a_subtask: "Añadir una subtarea",
+    duplicate: "Duplicar",
+    subtasks_completed: "Subtareas completadas",
+    due: "vence",
+    done: "hecha",
+    elapsed: Elapsed {
+        just_now: "ahora mismo",
+        before: "hace ",
+        minutes: " min",
+        hours: " h",
+        days: " d",
+        after: "",
+    },
+    calendar: "Calendario",
+    delete_subtasks_prompt: "Esta tarea tiene subtareas. ¿Qué hacemos con ellas?",
+    delete_subtasks: "Eliminarlas también",
+    promote_subtasks: "Conservarlas como tareas",
+    cancel: "Cancelar",
+    delete_subtasks_first: "Elimina antes sus subtareas",
+    months: [
+        "enero",
+        "febrero",
+        "marzo",
+        "abril",
+        "mayo",
+        "junio",
+        "julio",
+        "agosto",
+        "septiembre",
+        "octubre",
+        "noviembre",
+        "diciembre",
+    ],
+    weekdays: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+    previous_month: "Mes anterior",
+    next_month: "Mes siguiente",
+    changed_elsewhere: "Esta tarea se cambió en otro sitio desde que cargaste la página.",
+    your_list: "Tu lista",
+    current_list: "Lista actual",
+    reload: "Recargar",
+};
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, HeaderMap, HeaderValue};
+
+    use super::Strings;
+
+    fn headers(pairs: &[(header::HeaderName, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn negotiates_the_first_supported_language() {
+        let accept = |value| headers(&[(header::ACCEPT_LANGUAGE, value)]);
+        assert_eq!(Strings::negotiate(&HeaderMap::new()).lang, "en");
+        assert_eq!(Strings::negotiate(&accept("es-MX,es;q=0.9")).lang, "es");
+        assert_eq!(Strings::negotiate(&accept("fr-FR, es;q=0.5")).lang, "es");
+        assert_eq!(Strings::negotiate(&accept("fr-FR")).lang, "en");
+    }
+
+    #[test]
+    fn lang_cookie_wins_over_the_header() {
+        let headers = headers(&[
+            (header::ACCEPT_LANGUAGE, "en-US"),
+            (header::COOKIE, "id=abc; lang=es"),
+        ]);
+        assert_eq!(Strings::negotiate(&headers).lang, "es");
+    }
+}
@@ -0,0 +1,4062 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, LazyLock},
+};
+
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        DefaultBodyLimit, FromRequest, FromRequestParts, Path, Query, Request,
+    },
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, patch, post},
+    Extension, Form, Json, Router,
+};
+use chrono::{DateTime, Local, NaiveDate, Utc, Weekday};
+use maud::{html, Markup, DOCTYPE};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use surrealdb::{engine::any::Any, Surreal};
+use todos::TodoPlaceholder;
+use tower::ServiceBuilder;
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    compression::CompressionLayer,
+    limit::RequestBodyLimitLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, RequestId, SetRequestIdLayer},
+    timeout::TimeoutLayer,
+    trace::TraceLayer,
+};
+use tower_sessions::{
+    cookie::{
+        time::{Duration, OffsetDateTime},
+        Cookie, SameSite,
+    },
+    session::Id as SessionId,
+    ExpiredDeletion, Expiry, Session, SessionManagerLayer, SessionStore,
+};
+
+use crate::{
+    announce::Announcement,
+    archive::archive,
+    batch::{apply, batch, toggle_batch, BatchOp},
+    calendar::{calendar, due_on},
+    capacity::CapacityBanner,
+    config::{Config, SubtaskPolicy},
+    conflict::Conflict,
+    db::Database,
+    events::{Action, Event, Events},
+    filter::{Filter, ParseFilterError, TagFilter, TagMode},
+    footer::Footer,
+    help::Help,
+    i18n::{localize, Strings},
+    lists::ListTabs,
+    rate_limit::{rate_limit, RateLimiter},
+    session_store::{session_error, store_error, DatabaseSessionStore},
+    snapshot::{create_snapshot, snapshot, view_snapshot},
+    split::split_todo,
+    state::{AddPosition, Celebration, SortOrder, State, Theme},
+    todos::{CompletedStyle, Delta, List, LoadMore, Priority, Recurrence, Todo},
+};
+
+mod announce;
+mod archive;
+mod batch;
+mod calendar;
+mod capacity;
+pub mod config;
+mod conflict;
+pub(crate) mod db;
+mod events;
+mod filter;
+mod footer;
+mod help;
+mod i18n;
+mod lists;
+mod rate_limit;
+mod session_store;
+mod snapshot;
+mod split;
+mod state;
+mod todos;
+
+pub use db::connect;
+
+const STYLESHEET: &str = include_str!("style.css");
+/// The stylesheet is served as immutable, so its URL carries a hash of the contents to bust
+/// caches whenever it changes.
+static STYLESHEET_HREF: LazyLock<String> = LazyLock::new(|| {
+    let mut hasher = DefaultHasher::new();
+    STYLESHEET.hash(&mut hasher);
+    format!("/style.css?v={:x}", hasher.finish())
+});
+/// Carries [`State::version`], so a client holding a page rendered from an older version can tell
+/// it is stale and fetch it again.
+const STATE_VERSION_HEADER: &str = "x-state-version";
+/// Keeps the page's `data-state-version` at the version its last successful request left the
+/// state at. Pages send it back as [`STATE_VERSION_HEADER`], so a conflict can show what changed
+/// since.
+const TRACK_STATE_VERSION: &str = "const version = $event.detail.successful && \
+    $event.detail.xhr.getResponseHeader('x-state-version'); \
+    if (version) document.documentElement.dataset.stateVersion = version";
+/// Carry the footer's counts and the current filter, so scripts needn't parse them out of the HTML.
+const TODO_ACTIVE_HEADER: &str = "x-todo-active";
+const TODO_COMPLETED_HEADER: &str = "x-todo-completed";
+const TODO_FILTER_HEADER: &str = "x-todo-filter";
+/// The table the session store keeps sessions in.
+const SESSIONS_TABLE: &str = "sessions";
+/// Sent by clients that would rather receive only the todos a change touched than the whole list.
+const DELTA_HEADER: &str = "x-delta";
+/// The policy every HTML response is sent with. Alpine evaluates its attribute expressions with
+/// `new Function`, which is what needs `'unsafe-eval'`.
+const CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+    script-src 'self' 'unsafe-eval' https://unpkg.com https://cdn.jsdelivr.net; \
+    style-src 'self'; \
+    img-src 'self' data:; \
+    connect-src 'self'; \
+    base-uri 'self'; \
+    form-action 'self'; \
+    frame-ancestors 'none'";
+
+/// Builds every route with sessions and the audit log kept in `db`, each request served under
+/// `config`. Tasks deleting expired sessions and purging expired trash are spawned on the current
+/// runtime.
+pub fn build_router(db: Surreal<Any>, config: Config) -> Router {
+    let config = Arc::new(config);
+    let database = Database::new(db, config.clone());
+    let session_store = DatabaseSessionStore::new(database.clone(), SESSIONS_TABLE);
+    let expired_session_cleanup_interval = config.session_cleanup_interval_minutes.get();
+    tokio::task::spawn(session_store.clone().continuously_delete_expired(
+        tokio::time::Duration::from_secs(60 * expired_session_cleanup_interval),
+    ));
+    tokio::task::spawn(session_store.clone().continuously_purge_trash(
+        tokio::time::Duration::from_secs(60 * expired_session_cleanup_interval),
+        chrono::Duration::minutes(i64::from(config.soft_delete_grace_minutes)),
+    ));
+
+    // Layers run top to bottom on the request. Every response, even one for a panic, carries the
+    // request's id, and whatever is logged while serving it is traced under that id. A panic
+    // anywhere further in still gets a response. Compression wraps the finished response, session
+    // cookie and all. An oversized body is refused before the session is even loaded, and the
+    // timeout covers loading and saving the session as well as the handler.
+    let session_service = ServiceBuilder::new()
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .layer(CatchPanicLayer::custom(handle_panic))
+        .layer(CompressionLayer::new())
+        .layer(RequestBodyLimitLayer::new(config.max_body_bytes))
+        .layer(TimeoutLayer::new(std::time::Duration::from_secs(
+            config.request_timeout_secs,
+        )))
+        .layer(
+            SessionManagerLayer::new(session_store.clone())
+                .with_secure(config.cookie_secure)
+                .with_same_site(config.cookie_same_site)
+                .with_expiry(Expiry::OnInactivity(Duration::minutes(
+                    config.session_ttl_minutes.get().into(),
+                ))),
+        );
+
+    // Only the routes that touch the session's state report it, so fetching the stylesheet or the
+    // favicon doesn't load the session.
+    let stateful = Router::new()
+        .route("/", get(index))
+        .route("/active", get(index_active))
+        .route("/add", get(quick_add))
+        .route("/add-position", post(set_add_position))
+        .route("/admin/config", get(admin_config))
+        .route("/admin/sessions", get(admin_sessions))
+        .route("/admin/sessions/:id", delete(purge_session))
+        .route("/api/eta", get(eta))
+        .route("/archive", get(archive))
+        .route("/calendar", get(calendar))
+        .route("/due/:date", get(due_on))
+        .route("/api/session-ttl", get(session_ttl))
+        .route("/api/toggle-batch", post(toggle_batch))
+        .route("/api/weekly", get(weekly))
+        .route("/batch", post(batch))
+        .route("/celebration", post(set_celebration))
+        .route("/clear-completed", post(clear_completed))
+        .route("/completed", get(index_completed))
+        .route("/completed-style", post(set_completed_style))
+        .route("/count", get(count))
+        .route("/events", get(list_events))
+        .route("/export.csv", get(export_csv))
+        .route("/keepalive", post(keepalive))
+        .route("/focus", post(toggle_focus))
+        .route("/list", post(switch_list))
+        .route("/merge-session", post(merge_session))
+        .route("/search", post(search))
+        .route("/select", post(select_filter))
+        .route("/snapshot", post(create_snapshot))
+        .route("/sort", post(set_sort))
+        .route("/snapshots/:name", get(snapshot))
+        .route("/snapshots/:name/view", get(view_snapshot))
+        .route("/stale", get(stale))
+        .route("/tag", post(select_tag))
+        .route("/tag-matching", post(tag_matching))
+        .route("/filter/tags", get(filter_tags))
+        .route("/template/:id/instantiate", post(instantiate_template))
+        .route("/theme", post(set_theme))
+        .route("/todo", post(add_todo))
+        .route("/todo/:id", get(get_todo))
+        .route("/todo/:id", delete(delete_todo))
+        .route("/todo/:id", patch(patch_todo))
+        .route("/todo/:id/duplicate", post(duplicate_todo))
+        .route("/todo/:id/make-template", post(make_template))
+        .route("/todo/:id/restore", post(restore_todo))
+        .route("/todo/:id/split", post(split_todo))
+        .route("/todo/:id/subtask", post(add_subtask))
+        .route("/todo/:id/subtask/:sid", patch(patch_subtask))
+        .route("/todo/:id/subtask/:sid", delete(delete_subtask))
+        .route("/todo/:id/toggle", post(toggle_todo))
+        .route("/todo/:id/touch", post(touch_todo))
+        .route("/todos", delete(clear_todos))
+        .route("/todos/delete", post(delete_todos))
+        .route("/todos/page", get(page))
+        .route("/toggle-todos", post(toggle_todos))
+        .route("/trash", get(trash))
+        .route("/undo", post(undo))
+        .route("/week-start", post(set_week_start))
+        .route("/ws", get(ws))
+        .route_layer(middleware::from_fn(state_headers));
+
+    Router::new()
+        .route("/favicon.svg", get(favicon))
+        .route("/style.css", get(stylesheet))
+        .merge(stateful)
+        .fallback(not_found)
+        // The configured limit in `session_service` takes the place of axum's default.
+        .layer(DefaultBodyLimit::disable())
+        .layer(Extension(Events::new(database.clone())))
+        .layer(Extension(database))
+        .layer(Extension(session_store))
+        .layer(middleware::from_fn(security_headers))
+        .layer(middleware::from_fn(localize))
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::default(),
+            rate_limit,
+        ))
+        .layer(middleware::from_fn(request_id_in_errors))
+        .layer(Extension(config))
+        .layer(session_service)
+}
+
+/// The page under the session's own filter.
+async fn index(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    render_index(session, headers, None, &config, strings).await
+}
+
+/// The page under the active filter, for following the footer's links without JavaScript.
+async fn index_active(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    render_index(session, headers, Some(Filter::Active), &config, strings).await
+}
+
+/// The page under the completed filter, for following the footer's links without JavaScript.
+async fn index_completed(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    render_index(session, headers, Some(Filter::Completed), &config, strings).await
+}
+
+/// The whole page, under `filter` when given. That filter only applies to this render and is not
+/// saved, so `/` goes back to the session's own filter, which is All for anyone without htmx to
+/// change it. Answers 304 Not Modified when the client already holds the page for the current
+/// state.
+async fn render_index(
+    session: Session,
+    headers: HeaderMap,
+    filter: Option<Filter>,
+    config: &Config,
+    strings: &Strings,
+) -> Result<Response, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), config).await?;
+    // A session that has never been written starts from the filter in the cookie, if any.
+    if state.version == 0 {
+        if let Some(saved) = filter_from_cookie(&headers) {
+            if saved != state.filter {
+                state.filter = saved;
+                state.write(session).await?;
+            }
+        }
+    }
+    if let Some(filter) = filter {
+        state.filter = filter;
+    }
+    // The same state renders differently in each language.
+    let etag = format!(r#""{}-{}""#, state.etag().trim_matches('"'), strings.lang);
+    let cached = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if cached {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    let page = html! { (DOCTYPE) html lang=(strings.lang) data-theme=(state.theme.attr()) data-framework="axum-htmx-maud" data-state-version=(state.version) {
+        head {
+            meta charset="utf-8";
+            meta name="description" content="A demo of TodoMVC using axum, htmx, and maud";
+            meta name="viewport" content="width=device-width, initial-scale=1.0";
+            meta http-equiv="X-UA-Compatible" content="IE=edge";
+
+            script src="https://unpkg.com/htmx.org@1.9.11" integrity="sha384-0gxUXCCR8yv9FM2b+U3FDbsKthCI66oH5IA9fHppQq9DDMHuMauqq1ZHBpJxQ0J0" crossorigin="anonymous" { }
+            script src="https://unpkg.com/htmx.org@1.9.11/dist/ext/alpine-morph.js" { }
+            script defer src="https://cdn.jsdelivr.net/npm/alpinejs@3.x.x/dist/cdn.min.js" { }
+
+            link rel="stylesheet" href=(*STYLESHEET_HREF);
+            link rel="icon" type="image/svg+xml" href="/favicon.svg";
+
+            title { "todos (" (state.num_active()) ") - TodoMVC: axum, htmx, and maud" }
+        }
+
+        body x-data=(Help::DATA) "x-on:keydown.window"=(Help::keydown())
+            hx-headers=r#"js:{"X-Delta": "true", "X-State-Version": document.documentElement.dataset.stateVersion}"#
+            "x-on:htmx:after-request"=(TRACK_STATE_VERSION) "x-on:htmx:before-swap"=(Conflict::SHOW)
+            x-on:celebrate="$el.classList.add('celebrate'); setTimeout(() => $el.classList.remove('celebrate'), 1000)" {
+            (CapacityBanner { oob: false, ..CapacityBanner::new(&state, config, strings) })
+            section.todoapp {
+                header.header {
+                    h1 { (strings.title) }
+                    input.new-todo
+                        hx-post="/todo" hx-target="input[name='next-todo']" hx-include="input[name='next-todo']" hx-swap="outerHTML"
+                        data-clear-on-add=(config.clear_new_todo)
+                        x-data "x-on:htmx:after-request"="$event.detail.successful && $el.dataset.clearOnAdd === 'true' && ($event.target.value = '')"
+                        placeholder=(strings.placeholders.choose(&mut rand::thread_rng()).unwrap_or(&""))
+                        name="todo" autofocus;
+                    input.search type="search" name="q" value=(state.search) placeholder=(strings.search)
+                        hx-post="/search" hx-trigger="input changed delay:300ms, search"
+                        hx-target="#todo-list" hx-swap="outerHTML";
+                }
+
+                (ListTabs { oob: false, ..ListTabs::new(&state, strings) })
+                (List::new(&state, config, strings))
+            }
+
+            @let ttl = u64::from(config.session_ttl_minutes.get()) * 60;
+            div.session-warning x-cloak
+                x-data={ "{ left: " (ttl) " }" }
+                x-init="setInterval(() => left--, 1000)"
+                "x-on:htmx:after-request.window"={ "left = " (ttl) }
+                x-show={ "left <= " (config.session_warning_secs) } {
+                (strings.session_expiring) " "
+                button type="button" hx-post="/keepalive" hx-swap="none" { (strings.stay_signed_in) }
+            }
+
+            footer.info {
+                p { (strings.edit_hint) }
+                p {
+                    label for="theme" { (strings.theme) " " }
+                    select #theme name="theme" hx-post="/theme" hx-trigger="change" hx-swap="none"
+                        x-data x-on:change="document.documentElement.dataset.theme = $el.value.toLowerCase()" {
+                        @for (theme, name) in [(Theme::System, strings.system), (Theme::Light, strings.light), (Theme::Dark, strings.dark)] {
+                            option value=(format!("{theme:?}")) selected[theme == state.theme] { (name) }
+                        }
+                    }
+                }
+                p { (strings.press) " " kbd { "?" } " " (strings.for_keyboard_shortcuts) }
+                p { (strings.created_by) " " a href="https://garrettdavis.dev" { "Garrett Davis" } }
+                p { (strings.based_on) " " a href="http://todomvc.com" { "TodoMVC" } }
+            }
+
+            div.toast #toast role="status" { }
+            (Announcement::region())
+            (Conflict::region())
+
+            (Help(strings))
+        }
+    } };
+
+    Ok((
+        [
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, "no-cache".to_string()),
+        ],
+        page,
+    )
+        .into_response())
+}
+
+/// Traces each request in a span carrying its id, the one its error responses end with, so
+/// whatever is logged while serving it, a panic included, can be found from a report.
+fn request_span(request: &Request) -> tracing::Span {
+    let id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!("request", method = %request.method(), uri = %request.uri(), id)
+}
+
+fn handle_panic(err: Box<dyn std::any::Any + Send + 'static>) -> Response {
+    let message = err
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| err.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic");
+    tracing::error!("Handler panicked: {message}");
+
+    (StatusCode::INTERNAL_SERVER_ERROR, "internal server error").into_response()
+}
+
+/// htmx requests get a bare fragment to swap in, anything else a full page.
+async fn not_found(
+    Extension(strings): Extension<&'static Strings>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let message = html! {
+        p.not-found { (strings.nothing_here) " " a href="/" { (strings.back_to_todos) } }
+    };
+    if headers.contains_key("hx-request") {
+        return (StatusCode::NOT_FOUND, message);
+    }
+
+    (
+        StatusCode::NOT_FOUND,
+        html! { (DOCTYPE) html lang=(strings.lang) {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                link rel="stylesheet" href=(*STYLESHEET_HREF);
+                title { (strings.not_found) " - TodoMVC: axum, htmx, and maud" }
+            }
+            body {
+                section.todoapp {
+                    header.header { h1 { (strings.title) } }
+                    (message)
+                }
+            }
+        } },
+    )
+}
+
+/// Tags every response with the [`State::version`] it leaves the session at, and the counts and
+/// filter the footer would show for it.
+async fn state_headers(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    let Ok(state) = State::read(session, &config).await else {
+        return response;
+    };
+    let headers = response.headers_mut();
+    headers.insert(STATE_VERSION_HEADER, HeaderValue::from(state.version));
+    headers.insert(TODO_ACTIVE_HEADER, HeaderValue::from(state.num_active()));
+    headers.insert(
+        TODO_COMPLETED_HEADER,
+        HeaderValue::from(state.num_completed()),
+    );
+    headers.insert(
+        TODO_FILTER_HEADER,
+        HeaderValue::from_static(match state.filter {
+            Filter::All => "all",
+            Filter::Active => "active",
+            Filter::Completed => "completed",
+        }),
+    );
+    response
+}
+
+/// Ends plain-text error messages with the request's id, the inbound `X-Request-Id` or else a
+/// fresh UUID, so a report of the failure can be matched up with the logs.
+async fn request_id_in_errors(request: Request, next: Next) -> Response {
+    let id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .map(str::to_string);
+    let response = next.run(request).await;
+    let is_error = response.status().is_client_error() || response.status().is_server_error();
+    let is_text = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/plain"));
+    let Some(id) = id.filter(|_| is_error && is_text) else {
+        return response;
+    };
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(message) = axum::body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let message = format!("{} (request {id})", String::from_utf8_lossy(&message));
+    Response::from_parts(parts, message.into())
+}
+
+/// Adds [`CONTENT_SECURITY_POLICY`] and friends to HTML responses.
+async fn security_headers(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if is_html {
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static(CONTENT_SECURITY_POLICY),
+        );
+        headers.insert(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        );
+        headers.insert(
+            header::REFERRER_POLICY,
+            HeaderValue::from_static("same-origin"),
+        );
+    }
+    response
+}
+
+/// The tab icon, a badge with the number of active todos. Counts of 100 and up show as "99+".
+async fn favicon(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+    let count = match state.num_active() {
+        count @ 0..=99 => count.to_string(),
+        _ => "99+".to_string(),
+    };
+    let font_size = if count.len() > 2 { 26 } else { 36 };
+    let svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 64 64"><circle cx="32" cy="32" r="30" fill="#b83f45"/><text x="32" y="32" dy=".35em" text-anchor="middle" font-family="Helvetica, Arial, sans-serif" font-size="{font_size}" font-weight="bold" fill="#fff">{count}</text></svg>"##
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, "no-cache, no-store"),
+        ],
+        svg,
+    ))
+}
+
+async fn stylesheet() -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "text/css; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        STYLESHEET,
+    )
+}
+
+async fn clear_completed(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let cleared = state.clear_completed();
+    state.write(session.clone()).await?;
+    events
+        .record(&session, Action::Archived, cleared.iter().copied())
+        .await;
+
+    let announcement = Announcement::new(&state, Action::Archived, cleared.len(), strings);
+    if wants_delta(&headers) && !state.todos.is_empty() {
+        return Ok(delta(
+            &state,
+            Vec::new(),
+            cleared,
+            announcement,
+            &config,
+            strings,
+        ));
+    }
+
+    // Swap the whole list in place rather than out-of-band, so the footer it contains (and with
+    // it the "Clear completed" button) is replaced together with the remaining todos.
+    Ok(html! { (List { oob: false, ..List::new(&state, &config, strings) }) (CapacityBanner::new(&state, &config, strings)) (announcement) }
+        .into_response())
+}
+
+fn wants_delta(headers: &HeaderMap) -> bool {
+    headers
+        .get(DELTA_HEADER)
+        .is_some_and(|value| value.as_bytes() == b"true")
+}
+
+/// Answers with a [`Delta`] of the list, telling htmx to skip the usual swap into the target so
+/// only the out-of-band parts are applied.
+fn delta(
+    state: &State,
+    changed: Vec<u64>,
+    removed: Vec<u64>,
+    announcement: Announcement,
+    config: &Config,
+    strings: &Strings,
+) -> Response {
+    let changed = state
+        .todos
+        .iter()
+        .filter(|todo| changed.contains(&todo.id))
+        .collect();
+    (
+        [("hx-reswap", "none")],
+        html! {
+            (Delta { state, changed, removed, config, strings })
+            (CapacityBanner::new(state, config, strings))
+            (announcement)
+        },
+    )
+        .into_response()
+}
+
+/// Moves every todo to the trash. The filter is kept, and clearing an empty list is a no-op.
+async fn clear_todos(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let cleared = state.soft_delete(|_| true);
+    state.write(session.clone()).await?;
+    events.record(&session, Action::Deleted, cleared).await;
+
+    Ok(html! { (List { oob: false, ..List::new(&state, &config, strings) }) })
+}
+
+#[derive(Deserialize)]
+struct ListForm {
+    name: String,
+}
+/// Switches to the named list, creating it if needed, and swaps in its tabs and todos.
+async fn switch_list(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<ListForm>,
+) -> Result<Markup, (StatusCode, String)> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "list names may not be empty".to_string(),
+        ));
+    }
+
+    let mut state = State::read(session.clone(), &config).await?;
+    if name != state.active_list
+        && !state.lists.contains_key(name)
+        && state.lists.len() + 1 >= State::MAX_LISTS
+    {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("at most {} lists can be kept", State::MAX_LISTS),
+        ));
+    }
+    state.switch_list(name);
+    state.write(session).await?;
+
+    Ok(html! { (ListTabs::new(&state, strings)) (List::new(&state, &config, strings)) })
+}
+
+#[derive(Deserialize)]
+struct CompletedStyleForm {
+    completed_style: CompletedStyle,
+}
+async fn set_completed_style(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<CompletedStyleForm>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.completed_style = form.completed_style;
+    state.write(session).await?;
+
+    Ok(html! { (List::new(&state, &config, strings)) })
+}
+
+#[derive(Deserialize)]
+struct ThemeForm {
+    theme: Theme,
+}
+/// Saves the theme. The page switches its own `data-theme` as the choice is made, so there is
+/// nothing to swap in.
+async fn set_theme(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<ThemeForm>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.theme = form.theme;
+    state.write(session).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct CelebrationForm {
+    celebration: Celebration,
+}
+async fn set_celebration(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<CelebrationForm>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.celebration = form.celebration;
+    state.write(session).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+struct SelectForm {
+    filter: Filter,
+}
+async fn select_filter(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Form(q): Form<SelectForm>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.filter = q.filter;
+    state.write(session).await?;
+
+    Ok((
+        [(header::SET_COOKIE, filter_cookie(&state.filter, &config))],
+        html! { (List::new(&state, &config, strings)) },
+    ))
+}
+
+/// The last selected filter is also kept in its own long-lived cookie, so it outlives the session.
+const FILTER_COOKIE: &str = "filter";
+
+fn filter_cookie(filter: &Filter, config: &Config) -> String {
+    Cookie::build((FILTER_COOKIE, filter.to_string()))
+        .path("/")
+        .max_age(Duration::days(365))
+        .secure(config.cookie_secure)
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .build()
+        .to_string()
+}
+
+fn filter_from_cookie(headers: &HeaderMap) -> Option<Filter> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(Cookie::split_parse)
+        .filter_map(Result::ok)
+        .find(|cookie| cookie.name() == FILTER_COOKIE)
+        .and_then(|cookie| cookie.value().parse().ok())
+}
+
+#[derive(Deserialize)]
+struct SearchForm {
+    #[serde(default)]
+    q: String,
+}
+/// Lists only the todos matching `q`, or every todo again when it is empty.
+async fn search(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<SearchForm>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.search = form.q.trim().to_string();
+    state.write(session).await?;
+
+    Ok(html! { (List { oob: false, ..List::new(&state, &config, strings) }) })
+}
+
+/// Turns focus mode on or off.
+async fn toggle_focus(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.hide_completed = !state.hide_completed;
+    state.write(session).await?;
+
+    Ok(html! { (List { oob: false, ..List::new(&state, &config, strings) }) })
+}
+
+#[derive(Deserialize)]
+struct SortForm {
+    sort: SortOrder,
+}
+async fn set_sort(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<SortForm>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.sort = form.sort;
+    state.write(session).await?;
+
+    Ok(html! { (List { oob: false, ..List::new(&state, &config, strings) }) })
+}
+
+#[derive(Deserialize)]
+struct TagForm {
+    #[serde(default)]
+    tag: String,
+}
+/// Lists only the todos carrying the given tag, or every todo again when no tag is given.
+async fn select_tag(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<TagForm>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let tag = form.tag.trim().trim_start_matches('#').to_lowercase();
+    state.tag_filters = TagFilter {
+        tags: (!tag.is_empty()).then_some(tag).into_iter().collect(),
+        mode: TagMode::default(),
+    };
+    state.write(session).await?;
+
+    Ok(html! { (List { oob: false, ..List::new(&state, &config, strings) }) })
+}
+
+#[derive(Deserialize)]
+struct TagsQuery {
+    /// Comma-separated, with or without their `#`.
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    mode: TagMode,
+}
+/// Lists only the todos carrying all of the given tags, or with `mode=or` any of them, under the
+/// filter already selected. Without any tags every todo is listed again.
+async fn filter_tags(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(query): Query<TagsQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let mut tags: Vec<String> = Vec::new();
+    for tag in query.tags.split(',') {
+        let tag = tag.trim().trim_start_matches('#').to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    state.tag_filters = TagFilter {
+        tags,
+        mode: query.mode,
+    };
+    state.write(session).await?;
+
+    Ok(html! { (List { oob: false, ..List::new(&state, &config, strings) }) })
+}
+
+#[derive(Deserialize)]
+struct TagMatchingForm {
+    q: String,
+    tag: String,
+}
+/// Tags every todo matching the search `q`, and announces how many todos didn't already carry
+/// the tag.
+async fn tag_matching(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<TagMatchingForm>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let query = form.q.trim();
+    if query.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "the search may not be empty".to_string(),
+        ));
+    }
+    // A valid tag parses back out of `#tag` whole, leaving no description behind.
+    let (rest, tags) = Todo::parse_tags(&format!("#{}", form.tag.trim().trim_start_matches('#')));
+    let tag = match tags.as_slice() {
+        [tag] if rest.is_empty() => tag,
+        _ => return Err((StatusCode::BAD_REQUEST, "not a valid tag".to_string())),
+    };
+
+    let mut state = State::read(session.clone(), &config).await?;
+    let tagged = state.tag_matching(query, tag);
+    state.write(session.clone()).await?;
+    events
+        .record(&session, Action::Edited, tagged.clone())
+        .await;
+
+    Ok(html! {
+        (List { oob: false, ..List::new(&state, &config, strings) })
+        (Announcement::new(&state, Action::Edited, tagged.len(), strings))
+    })
+}
+
+#[derive(Deserialize)]
+struct NewTodo {
+    todo: String,
+    estimate: Option<u32>,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(rename = "next-todo")]
+    placeholder: TodoPlaceholder,
+    /// Adds the todo even when an active one already has the same description.
+    #[serde(default)]
+    force: bool,
+    due: Option<NaiveDate>,
+    recurrence: Option<Recurrence>,
+}
+async fn add_todo(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    Form(new_todo): Form<NewTodo>,
+) -> Result<Response, (StatusCode, String)> {
+    let description = Todo::sanitize_description(&new_todo.todo)?;
+
+    let mut state = State::read(session.clone(), &config).await?;
+    let idempotency_key = headers
+        .get("idempotency-key")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    // A retried request gets the todo its first attempt created, as long as it is still there.
+    if let Some(id) = idempotency_key
+        .as_deref()
+        .and_then(|key| state.created_for(key))
+    {
+        return Ok(match state.get(id) {
+            Some(todo) => added(
+                &state,
+                todo,
+                new_todo.placeholder,
+                &headers,
+                &config,
+                strings,
+            ),
+            None => html! { (List::new(&state, &config, strings)) }.into_response(),
+        });
+    }
+    state.check_capacity(&config)?;
+    let todo = Todo {
+        estimate_minutes: new_todo.estimate,
+        priority: new_todo.priority,
+        due: new_todo.due,
+        recurrence: new_todo.recurrence,
+        ..Todo::new(state.next_id(), &description)
+    };
+    if config.warn_duplicates && !new_todo.force {
+        if let Some(existing) = state.find_duplicate(&todo.description) {
+            let toast = html! {
+                div.toast #toast role="status" hx-swap-oob="true"
+                    x-data x-init="setTimeout(() => $el.replaceChildren(), 4000)" {
+                    "\"" (existing.description) "\" " (strings.already_on_your_list)
+                }
+            };
+            return Ok(html! { @match new_todo.placeholder {
+                TodoPlaceholder::FullPayload => (List { oob: false, ..List::new(&state, &config, strings) }) (toast),
+                TodoPlaceholder::Extend => (List::new(&state, &config, strings)) (toast),
+            } }
+            .into_response());
+        }
+    }
+    state.insert(todo.clone());
+    if let Some(key) = idempotency_key {
+        state.remember_created(key, todo.id);
+    }
+    state.write(session.clone()).await?;
+    events.record(&session, Action::Added, [todo.id]).await;
+
+    Ok(added(
+        &state,
+        &todo,
+        new_todo.placeholder,
+        &headers,
+        &config,
+        strings,
+    ))
+}
+
+/// The response to `todo` having been added to `state`.
+fn added(
+    state: &State,
+    todo: &Todo,
+    placeholder: TodoPlaceholder,
+    headers: &HeaderMap,
+    config: &Config,
+    strings: &Strings,
+) -> Response {
+    let body = html! {
+        (List::new(state, config, strings).added_fragment(todo, placeholder))
+        (CapacityBanner::new(state, config, strings))
+        (Announcement::new(state, Action::Added, 1, strings))
+    };
+
+    // htmx doesn't swap 201 responses by default, so only other clients are told about the new
+    // todo the RESTful way.
+    if headers.contains_key("hx-request") {
+        body.into_response()
+    } else {
+        let location = format!("/todo/{}", todo.id);
+        (StatusCode::CREATED, [(header::LOCATION, location)], body).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct QuickAdd {
+    text: String,
+}
+/// Adds a todo from a plain link, for bookmarks and OS shortcuts. Browsers say where a request
+/// came from in `Sec-Fetch-Site`, or failing that `Referer`, and links followed from other sites
+/// are refused so they can't add todos behind the user's back.
+async fn quick_add(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    Query(query): Query<QuickAdd>,
+) -> Result<Response, (StatusCode, String)> {
+    if !is_same_site(&headers) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "todos can only be added from this site or a bookmark".to_string(),
+        ));
+    }
+    let description = Todo::sanitize_description(&query.text)?;
+
+    let mut state = State::read(session.clone(), &config).await?;
+    state.check_capacity(&config)?;
+    let id = state.add(&description).id;
+    state.write(session.clone()).await?;
+    events.record(&session, Action::Added, [id]).await;
+
+    if headers.contains_key("hx-request") {
+        Ok(html! { (List::new(&state, &config, strings)) }.into_response())
+    } else {
+        Ok(Redirect::to("/").into_response())
+    }
+}
+
+fn is_same_site(headers: &HeaderMap) -> bool {
+    let get = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(site) = get(header::HeaderName::from_static("sec-fetch-site")) {
+        return site == "same-origin" || site == "none";
+    }
+    match (get(header::REFERER), get(header::HOST)) {
+        (Some(referer), Some(host)) => referer
+            .split_once("://")
+            .and_then(|(_, rest)| rest.split('/').next())
+            .is_some_and(|origin| origin == host),
+        (Some(_), None) => false,
+        (None, _) => true,
+    }
+}
+
+#[derive(Deserialize)]
+struct AddPositionForm {
+    add_position: AddPosition,
+}
+async fn set_add_position(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<AddPositionForm>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.add_position = form.add_position;
+    state.write(session).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The `:id` path parameter. Anything that isn't a positive integer is rejected with a plain 400
+/// rather than axum's default path rejection.
+#[derive(Deserialize)]
+struct Id {
+    id: u64,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Id {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(id) = Path::<Id>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| (StatusCode::BAD_REQUEST, "id must be a positive integer"))?;
+        Ok(id)
+    }
+}
+#[derive(Deserialize)]
+struct DeleteQuery {
+    /// What to do with the todo's subtasks, as chosen in the page's prompt.
+    subtasks: Option<SubtaskPolicy>,
+}
+/// Deletes a todo. Its subtasks go with it, become todos of their own, or keep it from being
+/// deleted, as `SUBTASK_DELETE_POLICY` says. Under the default policy the page asks which, and
+/// a request that doesn't say is refused with 400 Bad Request.
+async fn delete_todo(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    path: Id,
+    Query(query): Query<DeleteQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let has_subtasks = state
+        .get(path.id)
+        .is_some_and(|todo| !todo.subtasks.is_empty());
+    let policy = match config.subtask_delete_policy {
+        SubtaskPolicy::Prompt => query.subtasks.unwrap_or(SubtaskPolicy::Prompt),
+        policy => policy,
+    };
+    let promoted = match policy {
+        _ if !has_subtasks => Vec::new(),
+        SubtaskPolicy::Prompt => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "the todo has subtasks, say whether to delete or promote them with ?subtasks="
+                    .to_string(),
+            ))
+        }
+        SubtaskPolicy::Block => {
+            return Err((
+                StatusCode::CONFLICT,
+                "the todo has subtasks, delete them first".to_string(),
+            ))
+        }
+        SubtaskPolicy::Delete => Vec::new(),
+        SubtaskPolicy::Promote => state.promote_subtasks(path.id),
+    };
+    let removed = state.remove(path.id);
+    if !promoted.is_empty() {
+        state.write(session.clone()).await?;
+        events.record(&session, Action::Deleted, [path.id]).await;
+        events.record(&session, Action::Added, promoted).await;
+        return Ok(html! {
+            (List::new(&state, &config, strings))
+            (Announcement::new(&state, Action::Deleted, 1, strings))
+        });
+    }
+    let announcement = Announcement::new(&state, Action::Deleted, usize::from(removed), strings);
+    let footer = Footer::new(&state, &config, strings);
+    state.write(session.clone()).await?;
+    // The todo is removed out-of-band too, so it goes whichever element sent the request.
+    let response = html! {
+        @if removed { li #{"todo-" (path.id)} hx-swap-oob="delete" { } }
+        (footer)
+        (CapacityBanner::new(&state, &config, strings))
+        (announcement)
+    };
+    events
+        .record(&session, Action::Deleted, removed.then_some(path.id))
+        .await;
+    Ok(response)
+}
+
+async fn restore_todo(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    path: Id,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.check_capacity(&config)?;
+    if !state.restore(path.id) {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    state.write(session.clone()).await?;
+    events.record(&session, Action::Restored, [path.id]).await;
+
+    Ok(
+        html! { (List::new(&state, &config, strings)) (Announcement::new(&state, Action::Restored, 1, strings)) }
+            .into_response(),
+    )
+}
+
+async fn undo(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let restored = state.undo();
+    state.write(session.clone()).await?;
+    let announcement = Announcement::new(&state, Action::Restored, restored.len(), strings);
+    events.record(&session, Action::Restored, restored).await;
+
+    Ok(html! { (List::new(&state, &config, strings)) (announcement) })
+}
+
+async fn trash(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+    Ok(Json(state.trash))
+}
+
+/// Adds an active copy of a todo right after it. The copy is inserted next to the original where
+/// the list shows it there, and otherwise the whole list is sent.
+async fn duplicate_todo(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    path: Id,
+) -> Result<Response, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.check_capacity(&config)?;
+    let Some(copy) = state.duplicate(path.id).cloned() else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    state.write(session.clone()).await?;
+    events.record(&session, Action::Added, [copy.id]).await;
+
+    let in_place = state.sort == SortOrder::Added
+        && state.shows(&copy)
+        && state
+            .get(path.id)
+            .is_some_and(|original| state.shows(original));
+    Ok(html! {
+        @if in_place {
+            // htmx inserts the children of an out-of-band element swapped in any way but outerHTML.
+            div hx-swap-oob={ "afterend:#todo-" (path.id) } { (copy.render(&config, strings)) }
+            (Footer::new(&state, &config, strings))
+        } @else {
+            (List::new(&state, &config, strings))
+        }
+        (CapacityBanner::new(&state, &config, strings))
+        (Announcement::new(&state, Action::Added, 1, strings))
+    }
+    .into_response())
+}
+
+async fn make_template(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    path: Id,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let Some(index) = state.todos.iter().position(|todo| todo.id == path.id) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let mut template = state.todos.remove(index);
+    template.set_completed(false);
+    state.templates.push(template);
+    state.write(session.clone()).await?;
+    events.record(&session, Action::Templated, [path.id]).await;
+
+    Ok(html! { (List::new(&state, &config, strings)) }.into_response())
+}
+
+async fn instantiate_template(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    path: Id,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.check_capacity(&config)?;
+    let Some(template) = state
+        .templates
+        .iter()
+        .find(|todo| todo.id == path.id)
+        .cloned()
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let id = state.next_id();
+    state.insert(Todo {
+        id,
+        updated_at: Utc::now(),
+        ..template
+    });
+    state.write(session.clone()).await?;
+    events.record(&session, Action::Added, [id]).await;
+
+    Ok(html! { (List::new(&state, &config, strings)) }.into_response())
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    offset: usize,
+}
+/// The list's todos from `offset` on, one page of them, followed by a [`LoadMore`] for the next
+/// page if there is one.
+async fn page(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(query): Query<PageQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+    let page_size = config.page_size.get();
+    let visible = state.visible();
+    let next = query.offset.saturating_add(page_size);
+
+    Ok(html! {
+        @for todo in visible.iter().skip(query.offset).take(page_size) {
+            (todo.render_matching(&state.search, &config, strings))
+        }
+        @if visible.len() > next { (LoadMore { offset: next, strings }) }
+    })
+}
+
+/// A list of todo ids, either as a JSON `{ "ids": [..] }` body or as repeated `ids[]` form fields.
+#[derive(Deserialize)]
+struct IdList {
+    ids: Vec<u64>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for IdList {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_json = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json"));
+
+        if is_json {
+            let Json(list) = Json::<IdList>::from_request(req, state)
+                .await
+                .map_err(|err| (StatusCode::BAD_REQUEST, err.body_text()).into_response())?;
+            Ok(list)
+        } else {
+            let body = Bytes::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            let ids = form_urlencoded::parse(&body)
+                .filter(|(key, _)| key == "ids[]" || key == "ids")
+                .map(|(_, value)| value.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| {
+                    (StatusCode::BAD_REQUEST, "ids must be positive integers").into_response()
+                })?;
+            Ok(IdList { ids })
+        }
+    }
+}
+async fn delete_todos(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    list: IdList,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let deleted = state.soft_delete(|todo| list.ids.contains(&todo.id));
+    state.write(session.clone()).await?;
+    events
+        .record(&session, Action::Deleted, deleted.clone())
+        .await;
+
+    let announcement = Announcement::new(&state, Action::Deleted, deleted.len(), strings);
+    if wants_delta(&headers) && !state.todos.is_empty() {
+        return Ok(delta(
+            &state,
+            Vec::new(),
+            deleted,
+            announcement,
+            &config,
+            strings,
+        ));
+    }
+    Ok(
+        html! { (List::new(&state, &config, strings)) (CapacityBanner::new(&state, &config, strings)) (announcement) }
+            .into_response(),
+    )
+}
+
+/// A single todo, as the list renders it.
+async fn get_todo(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    path: Id,
+) -> Result<Response, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+    Ok(match state.get(path.id) {
+        Some(todo) => todo
+            .render_matching(&state.search, &config, strings)
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    })
+}
+
+/// Changes to a todo, as a form or as JSON. Only JSON may set `completed`, taken as the value to
+/// set; the page toggles completion with `POST /todo/:id/toggle` instead.
+#[derive(Debug, Deserialize)]
+struct PatchTodo {
+    completed: Option<bool>,
+    desc: Option<String>,
+    estimate: Option<u32>,
+    priority: Option<Priority>,
+    /// The version of the todo the change was made against, refused with 409 if it has moved on.
+    version: Option<u64>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for PatchTodo {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_json = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json"));
+
+        if is_json {
+            let Json(patch) = Json::<PatchTodo>::from_request(req, state)
+                .await
+                .map_err(|err| (StatusCode::BAD_REQUEST, err.body_text()).into_response())?;
+            Ok(patch)
+        } else {
+            let Form(patch) = Form::<PatchTodo>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(PatchTodo {
+                completed: None,
+                ..patch
+            })
+        }
+    }
+}
+async fn patch_todo(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    path: Id,
+    body: PatchTodo,
+) -> Result<Response, (StatusCode, String)> {
+    let description = body
+        .desc
+        .as_deref()
+        .map(Todo::sanitize_description)
+        .transpose()?;
+
+    let mut state = State::read(session.clone(), &config).await?;
+
+    if let Some(todo) = state.todos.iter_mut().find(|todo| todo.id == path.id) {
+        if body.version.is_some_and(|version| version != todo.version) {
+            return Ok(conflict(&state, &headers, strings));
+        }
+
+        let edited = body.desc.is_some() || body.estimate.is_some() || body.priority.is_some();
+        let completion = body
+            .completed
+            .filter(|&completed| completed != todo.completed)
+            .map(|completed| {
+                todo.set_completed(completed);
+                if completed {
+                    Action::Completed
+                } else {
+                    Action::Uncompleted
+                }
+            });
+        if let Some(description) = description {
+            todo.edit_description(&description);
+        }
+        if let Some(estimate) = body.estimate {
+            todo.estimate_minutes = Some(estimate);
+        }
+        if let Some(priority) = body.priority {
+            todo.priority = priority;
+        }
+        if edited || completion.is_some() {
+            todo.touch();
+        }
+
+        // Edits never change what the footer counts, so it is only sent along when completion
+        // changed too. Edits are only sent on Enter, so typing doesn't send one per keystroke.
+        // Completing a recurring todo adds its next occurrence, so then the whole list is sent.
+        let recurs = completion == Some(Action::Completed) && todo.recurrence.is_some();
+        let (result, recurred) = if recurs {
+            let recurred = state.recur(path.id);
+            let list = html! {
+                (List::new(&state, &config, strings)) (Announcement::new(&state, Action::Completed, 1, strings))
+            };
+            (list, recurred)
+        } else {
+            let result = html! {
+                (todo.render(&config, strings))
+                @if let Some(action) = completion {
+                    (Footer::new(&state, &config, strings)) (Announcement::new(&state, action, 1, strings))
+                } @else if edited {
+                    (Announcement::new(&state, Action::Edited, 1, strings))
+                }
+            };
+            (result, None)
+        };
+        state.write(session.clone()).await?;
+        // Both changes are saved in the one write above, and logged after it.
+        for action in completion
+            .into_iter()
+            .chain(edited.then_some(Action::Edited))
+        {
+            events.record(&session, action, [path.id]).await;
+        }
+        events.record(&session, Action::Added, recurred).await;
+
+        Ok(celebrate(
+            &state,
+            completion == Some(Action::Completed),
+            result,
+        ))
+    } else {
+        Ok(html! {}.into_response())
+    }
+}
+
+/// The 409 refusing a change made against a todo that has moved on. When the page said which
+/// version of the state it was rendered from, and that version is still remembered, it shows how
+/// the list changed since.
+fn conflict(state: &State, headers: &HeaderMap, strings: &Strings) -> Response {
+    let stale = headers
+        .get(STATE_VERSION_HEADER)
+        .and_then(|value| value.to_str().ok()?.parse().ok())
+        .and_then(|version| state.todos_at(version));
+    match stale {
+        Some(stale) => (
+            StatusCode::CONFLICT,
+            html! { (Conflict { stale, current: &state.todos, strings }) },
+        )
+            .into_response(),
+        None => (
+            StatusCode::CONFLICT,
+            "the todo was changed elsewhere, reload to see the latest version",
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct ToggleForm {
+    /// The version of the todo the toggle was made against, refused with 409 if it has moved on.
+    version: Option<u64>,
+}
+/// Flips a single todo between active and completed.
+async fn toggle_todo(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    path: Id,
+    Form(form): Form<ToggleForm>,
+) -> Result<Response, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let stale = state
+        .get(path.id)
+        .zip(form.version)
+        .is_some_and(|(todo, version)| version != todo.version);
+    if stale {
+        return Ok(conflict(&state, &headers, strings));
+    }
+    let Some(todo) = state.toggle(path.id).cloned() else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let completed = todo.completed;
+    let action = if completed {
+        Action::Completed
+    } else {
+        Action::Uncompleted
+    };
+    // Focus mode folds a completed todo into the count below the list, and completing a recurring
+    // todo adds its next occurrence to it, so the whole list is sent.
+    let recurred = completed && todo.recurrence.is_some();
+    let result = if state.hide_completed || recurred {
+        html! { (List::new(&state, &config, strings)) (Announcement::new(&state, action, 1, strings)) }
+    } else {
+        html! { (todo.render(&config, strings)) (Footer::new(&state, &config, strings)) (Announcement::new(&state, action, 1, strings)) }
+    };
+    state.write(session.clone()).await?;
+    events.record(&session, action, [path.id]).await;
+
+    Ok(celebrate(&state, completed, result))
+}
+
+async fn toggle_todos(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    // Only the todos the list shows are toggled, so one hidden by the filter never needs showing.
+    let all_completed = state
+        .todos
+        .iter()
+        .filter(|todo| state.shows(todo))
+        .all(|todo| todo.completed);
+    let toggled: Vec<u64> = state
+        .todos
+        .iter()
+        .filter(|todo| state.shows(todo) && todo.completed == all_completed)
+        .map(|todo| todo.id)
+        .collect();
+    state
+        .todos
+        .iter_mut()
+        .filter(|todo| toggled.contains(&todo.id))
+        .for_each(|todo| {
+            todo.set_completed(!all_completed);
+            todo.touch();
+        });
+    let recurred: Vec<u64> = if all_completed {
+        Vec::new()
+    } else {
+        toggled.iter().filter_map(|&id| state.recur(id)).collect()
+    };
+    state.write(session.clone()).await?;
+    let action = if all_completed {
+        Action::Uncompleted
+    } else {
+        Action::Completed
+    };
+    events.record(&session, action, toggled.clone()).await;
+    events
+        .record(&session, Action::Added, recurred.clone())
+        .await;
+    let announcement = Announcement::new(&state, action, toggled.len(), strings);
+    // A delta can't update the count of todos folded away by focus mode, nor add the next
+    // occurrences of recurring todos.
+    if wants_delta(&headers) && !state.hide_completed && recurred.is_empty() {
+        let response = delta(&state, toggled, Vec::new(), announcement, &config, strings);
+        return Ok(celebrate(&state, !all_completed, response));
+    }
+    Ok(celebrate(
+        &state,
+        !all_completed,
+        html! { (List::new(&state, &config, strings)) (announcement) },
+    ))
+}
+
+/// Fires the session's [`Celebration`] through `HX-Trigger` when todos were just completed.
+fn celebrate(state: &State, completed: bool, response: impl IntoResponse) -> Response {
+    let mut response = response.into_response();
+    if let Some(trigger) = state.celebration.trigger().filter(|_| completed) {
+        response
+            .headers_mut()
+            .insert("hx-trigger", HeaderValue::from_static(trigger));
+    }
+    response
+}
+
+/// A change sent over `GET /ws`, as a JSON text message.
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Command {
+    Add { text: String },
+    Toggle { id: u64 },
+    Delete { id: u64 },
+    Filter { filter: Filter },
+}
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Reply {
+    /// The list as it stands after the command, as `POST /list` and friends render it.
+    List {
+        list: String,
+    },
+    Error {
+        error: String,
+    },
+}
+/// Upgrades to a WebSocket taking [`Command`]s, for clients that would rather keep one connection
+/// open than make a request per change. Each command is answered with the list it leaves, or with
+/// why it failed, and the connection stays open either way.
+async fn ws(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    // Browsers let any site open a WebSocket, so only this one may open one with the session.
+    if !is_same_site(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    upgrade.on_upgrade(move |socket| run_commands(socket, session, events, config, strings))
+}
+
+async fn run_commands(
+    mut socket: WebSocket,
+    session: Session,
+    events: Events,
+    config: Arc<Config>,
+    strings: &'static Strings,
+) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let reply = match message {
+            Message::Text(text) => match serde_json::from_str(&text) {
+                Ok(command) => run_command(&session, &events, command, &config, strings).await,
+                Err(err) => Reply::Error {
+                    error: err.to_string(),
+                },
+            },
+            Message::Close(_) => break,
+            // Pings are answered by axum itself, and nothing is expected as binary.
+            _ => continue,
+        };
+        let reply = serde_json::to_string(&reply).unwrap_or_default();
+        if socket.send(Message::Text(reply)).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn run_command(
+    session: &Session,
+    events: &Events,
+    command: Command,
+    config: &Config,
+    strings: &Strings,
+) -> Reply {
+    let mut state = match State::read(session.clone(), config).await {
+        Ok(state) => state,
+        Err((_, error)) => return Reply::Error { error },
+    };
+    let applied = match command {
+        Command::Add { text } => apply(&mut state, BatchOp::Add { text }, config).map(Some),
+        Command::Toggle { id } => apply(&mut state, BatchOp::Toggle { id }, config).map(Some),
+        Command::Delete { id } => apply(&mut state, BatchOp::Delete { id }, config).map(Some),
+        Command::Filter { filter } => {
+            state.filter = filter;
+            Ok(None)
+        }
+    };
+    let applied = match applied {
+        Ok(applied) => applied,
+        Err(error) => return Reply::Error { error },
+    };
+
+    if let Err((_, error)) = state.write(session.clone()).await {
+        return Reply::Error { error };
+    }
+    // The session layer saved what there was to save when the upgrade was answered, so changes
+    // made over the socket are saved as they happen.
+    if let Err(err) = session.save().await {
+        return Reply::Error {
+            error: err.to_string(),
+        };
+    }
+    if let Some((action, id)) = applied {
+        events.record(session, action, [id]).await;
+    }
+
+    let list = html! { (List::new(&state, config, strings)) }.into_string();
+    Reply::List { list }
+}
+
+#[derive(Deserialize)]
+struct WeekStartForm {
+    week_start: Weekday,
+}
+async fn set_week_start(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<WeekStartForm>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    state.week_start = form.week_start;
+    state.write(session).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize)]
+struct Eta {
+    date: NaiveDate,
+}
+async fn eta(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+    let date = state.eta(Local::now().date_naive(), config.daily_capacity_minutes);
+
+    Ok(Json(Eta { date }))
+}
+
+#[derive(Serialize)]
+struct Week {
+    /// The day the week starts on, under the session's start of the week.
+    start: NaiveDate,
+    completed: usize,
+}
+/// How many todos were completed each week, oldest week first. Weeks with none are left out.
+async fn weekly(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+    let weeks: Vec<Week> = state
+        .completed_by_week()
+        .into_iter()
+        .map(|(start, completed)| Week { start, completed })
+        .collect();
+
+    Ok(Json(weeks))
+}
+
+#[derive(Serialize)]
+struct Count {
+    active: usize,
+    completed: usize,
+    total: usize,
+}
+async fn count(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+
+    Ok(Json(Count {
+        active: state.num_active(),
+        completed: state.num_completed(),
+        total: state.todos.len(),
+    }))
+}
+
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    id: u64,
+    description: &'a str,
+    completed: bool,
+    priority: Priority,
+    estimate_minutes: Option<u32>,
+    tags: String,
+    updated_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+}
+#[derive(Deserialize)]
+struct ExportQuery {
+    filter: Option<String>,
+}
+/// The session's todos as a CSV download, one row per todo in list order. `?filter=` narrows them
+/// down to the active or completed ones.
+async fn export_csv(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let filter: Filter = match query.filter {
+        Some(filter) => filter
+            .parse()
+            .map_err(|err: ParseFilterError| (StatusCode::BAD_REQUEST, err.to_string()))?,
+        None => Filter::All,
+    };
+    let state = State::read(session, &config).await?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for todo in state.todos.iter().filter(|todo| filter.matches(todo)) {
+        writer
+            .serialize(CsvRow {
+                id: todo.id,
+                description: &todo.description,
+                completed: todo.completed,
+                priority: todo.priority,
+                estimate_minutes: todo.estimate_minutes,
+                tags: todo.tags.join(" "),
+                updated_at: todo.updated_at,
+                completed_at: todo.completed_at,
+            })
+            .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    }
+    let csv = writer
+        .into_inner()
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                r#"attachment; filename="todos.csv""#,
+            ),
+        ],
+        csv,
+    ))
+}
+
+fn default_stale_days() -> u32 {
+    7
+}
+#[derive(Deserialize)]
+struct StaleQuery {
+    #[serde(default = "default_stale_days")]
+    days: u32,
+}
+/// Active todos left unchanged for more than `days` days, oldest first, each with buttons to bump
+/// it or complete it on the spot.
+async fn stale(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+    Query(query): Query<StaleQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+    let cutoff = Utc::now() - chrono::Duration::days(query.days.into());
+    let mut stale: Vec<&Todo> = state
+        .todos
+        .iter()
+        .filter(|todo| !todo.completed && todo.updated_at < cutoff)
+        .collect();
+    stale.sort_by_key(|todo| todo.updated_at);
+
+    Ok(html! { ul.stale-list {
+        @for todo in stale {
+            li #{"stale-" (todo.id)} hx-target="this" hx-swap="delete" {
+                span { (todo.description) }
+                " "
+                time datetime=(todo.updated_at.to_rfc3339()) {
+                    "untouched since " (todo.updated_at.format("%Y-%m-%d"))
+                }
+                button hx-post={"/todo/" (todo.id) "/touch"} { "Touch" }
+                button hx-post={"/todo/" (todo.id) "/toggle"} { "Complete" }
+            }
+        }
+    } })
+}
+
+/// The `:id` and `:sid` path parameters of a todo's subtask.
+#[derive(Deserialize)]
+struct SubtaskId {
+    id: u64,
+    sid: u64,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for SubtaskId {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(id) = Path::<SubtaskId>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| (StatusCode::BAD_REQUEST, "ids must be positive integers"))?;
+        Ok(id)
+    }
+}
+
+#[derive(Deserialize)]
+struct NewSubtask {
+    description: String,
+}
+async fn add_subtask(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    path: Id,
+    Form(form): Form<NewSubtask>,
+) -> Result<Response, (StatusCode, String)> {
+    let description = Todo::sanitize_description(&form.description)?;
+    let mut state = State::read(session.clone(), &config).await?;
+    let id = state.next_id();
+    let Some(todo) = state.todos.iter_mut().find(|todo| todo.id == path.id) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    todo.add_subtask(id, &description);
+    todo.touch();
+    let result = html! { (todo.render(&config, strings)) };
+    state.write(session).await?;
+
+    Ok(result.into_response())
+}
+
+#[derive(Deserialize)]
+struct PatchSubtask {
+    completed: Option<bool>,
+    description: Option<String>,
+}
+/// Edits a subtask. With `COMPLETE_WITH_SUBTASKS` set, completing the last active one completes
+/// the todo too.
+async fn patch_subtask(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    path: SubtaskId,
+    Form(form): Form<PatchSubtask>,
+) -> Result<Response, (StatusCode, String)> {
+    let description = form
+        .description
+        .as_deref()
+        .map(Todo::sanitize_description)
+        .transpose()?;
+    let mut state = State::read(session.clone(), &config).await?;
+    let Some(todo) = state.todos.iter_mut().find(|todo| todo.id == path.id) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let Some(subtask) = todo
+        .subtasks
+        .iter_mut()
+        .find(|subtask| subtask.id == path.sid)
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    if let Some(completed) = form.completed {
+        subtask.completed = completed;
+    }
+    if let Some(description) = description {
+        subtask.description = description;
+    }
+    let (completed, total) = todo.subtask_progress();
+    let completes = config.complete_with_subtasks && !todo.completed && completed == total;
+    if completes {
+        todo.set_completed(true);
+    }
+    todo.touch();
+
+    // As with completing it directly, a recurring todo then adds its next occurrence.
+    let recurred = completes.then(|| state.recur(path.id)).flatten();
+    let result = if recurred.is_some() {
+        html! { (List::new(&state, &config, strings)) (Announcement::new(&state, Action::Completed, 1, strings)) }
+    } else {
+        html! {
+            @if let Some(todo) = state.get(path.id) { (todo.render(&config, strings)) }
+            @if completes {
+                (Footer::new(&state, &config, strings)) (Announcement::new(&state, Action::Completed, 1, strings))
+            }
+        }
+    };
+    state.write(session.clone()).await?;
+    if completes {
+        events.record(&session, Action::Completed, [path.id]).await;
+    }
+    events.record(&session, Action::Added, recurred).await;
+
+    Ok(celebrate(&state, completes, result))
+}
+
+async fn delete_subtask(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    path: SubtaskId,
+) -> Result<Response, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let Some(todo) = state.todos.iter_mut().find(|todo| todo.id == path.id) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let count = todo.subtasks.len();
+    todo.subtasks.retain(|subtask| subtask.id != path.sid);
+    if todo.subtasks.len() == count {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    todo.touch();
+    let result = html! { (todo.render(&config, strings)) };
+    state.write(session).await?;
+
+    Ok(result.into_response())
+}
+
+async fn touch_todo(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+    path: Id,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut state = State::read(session.clone(), &config).await?;
+    let Some(todo) = state.todos.iter_mut().find(|todo| todo.id == path.id) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    todo.touch();
+    state.write(session).await?;
+
+    // htmx doesn't swap on 204, so answer with an empty body for the stale entry to be removed.
+    Ok(html! {}.into_response())
+}
+
+#[derive(Serialize)]
+struct SessionTtl {
+    seconds: i64,
+}
+/// How long the session had left before this request. The session is only saved, and its expiry
+/// pushed back, once the response is on its way, so the stored expiry is still the old one here.
+async fn session_ttl(
+    session: Session,
+    Extension(store): Extension<DatabaseSessionStore>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Json<SessionTtl>, (StatusCode, String)> {
+    let record = match session.id() {
+        Some(id) => store.load(&id).await.map_err(store_error)?,
+        None => None,
+    };
+    let seconds = match record {
+        Some(record) => (record.expiry_date - OffsetDateTime::now_utc()).whole_seconds(),
+        None => Duration::minutes(config.session_ttl_minutes.get().into()).whole_seconds(),
+    };
+
+    Ok(Json(SessionTtl {
+        seconds: seconds.max(0),
+    }))
+}
+
+/// Saves the session, which pushes its expiry back by the full inactivity timeout.
+async fn keepalive(session: Session) -> Result<StatusCode, (StatusCode, String)> {
+    session.save().await.map_err(session_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_events(
+    session: Session,
+    Extension(events): Extension<Events>,
+) -> Result<Json<Vec<Event>>, (StatusCode, String)> {
+    events
+        .for_session(&session)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+/// Only lets a request through when it carries `Authorization: Bearer <ADMIN_PASSWORD>`. Without a
+/// configured password the admin routes don't exist.
+struct Admin;
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Admin {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(config) = Extension::<Arc<Config>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let password = config
+            .admin_password
+            .as_deref()
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let given = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        // Compared in constant time, so how long the comparison takes doesn't give away how much
+        // of the password was right.
+        if given.is_some_and(|given| bool::from(given.as_bytes().ct_eq(password.as_bytes()))) {
+            Ok(Admin)
+        } else {
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+async fn admin_config(_: Admin, Extension(config): Extension<Arc<Config>>) -> Response {
+    Json(config.public()).into_response()
+}
+
+#[derive(Serialize)]
+struct SessionSummary {
+    id: String,
+    /// How many todos the session holds, across all its lists.
+    todos: usize,
+}
+
+/// Every unexpired session in the store.
+async fn admin_sessions(
+    _: Admin,
+    Extension(store): Extension<DatabaseSessionStore>,
+) -> Result<Json<Vec<SessionSummary>>, (StatusCode, String)> {
+    let mut sessions = Vec::new();
+    for session_id in store.ids().await.map_err(store_error)? {
+        // Expired sessions not yet cleaned up load as nothing.
+        let Some(mut record) = store.load(&session_id).await.map_err(store_error)? else {
+            continue;
+        };
+        let state = match record.data.remove(State::KEY) {
+            Some(value) => State::deserialize(value)
+                .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?,
+            None => State::default(),
+        };
+        let todos = state.todos.len() + state.lists.values().map(Vec::len).sum::<usize>();
+        sessions.push(SessionSummary {
+            id: session_id.to_string(),
+            todos,
+        });
+    }
+    Ok(Json(sessions))
+}
+
+/// Deletes a session, signing out whoever holds it.
+async fn purge_session(
+    _: Admin,
+    Extension(store): Extension<DatabaseSessionStore>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let session_id: SessionId = id
+        .parse()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "invalid session id".to_string()))?;
+    store.delete(&session_id).await.map_err(store_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct MergeSessionForm {
+    /// The id of the session to take todos from, as found in its session cookie.
+    session: String,
+}
+/// Appends each of another session's lists to the list of the same name in this one, and deletes
+/// that session. The todos are given new ids from this session.
+async fn merge_session(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(store): Extension<DatabaseSessionStore>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<MergeSessionForm>,
+) -> Result<Markup, (StatusCode, String)> {
+    let source_id: SessionId = form
+        .session
+        .parse()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "invalid session id".to_string()))?;
+    if session.id() == Some(source_id) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "a session can't be merged into itself".to_string(),
+        ));
+    }
+
+    let mut record = store
+        .load(&source_id)
+        .await
+        .map_err(store_error)?
+        .ok_or((StatusCode::NOT_FOUND, "no such session".to_string()))?;
+    let source = match record.data.remove(State::KEY) {
+        Some(value) => State::deserialize(value)
+            .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?,
+        None => State::default(),
+    };
+
+    let mut state = State::read(session.clone(), &config).await?;
+    let merged = state.merge(source, &config)?;
+    state.write(session.clone()).await?;
+    store.delete(&source_id).await.map_err(store_error)?;
+    events.record(&session, Action::Added, merged).await;
+
+    Ok(html! { (ListTabs::new(&state, strings)) (List::new(&state, &config, strings)) })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        num::NonZeroUsize,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use axum::{
+        async_trait,
+        body::{to_bytes, Body},
+        http::{header, Method, Request, StatusCode},
+        middleware,
+        response::Response,
+        routing::{get, post},
+        Extension, Router,
+    };
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+    use tower::ServiceExt;
+    use tower_http::{catch_panic::CatchPanicLayer, timeout::TimeoutLayer};
+    use tower_sessions::{
+        session::{Id as SessionId, Record},
+        session_store, SessionManagerLayer, SessionStore,
+    };
+
+    use super::{add_todo, handle_panic, index, state_headers, STATE_VERSION_HEADER};
+    use crate::{
+        build_router,
+        config::{Config, SubtaskPolicy},
+        db::{self, Database},
+        events::Events,
+        i18n::localize,
+        todos::Todo,
+    };
+
+    /// Drives the app the way a browser would, carrying the session cookie between requests.
+    struct Client {
+        app: Router,
+        cookie: Option<String>,
+    }
+
+    impl Client {
+        async fn new() -> Self {
+            Self::with_config(Config::default()).await
+        }
+
+        /// A client of the whole app, serving `config` from a fresh database.
+        async fn with_config(config: Config) -> Self {
+            let db = db::connect(&config).await.unwrap();
+            Self::with_router(build_router(db, config))
+        }
+
+        /// A client of just the index and the add form, keeping sessions in `store`.
+        async fn with_store(store: impl SessionStore + Clone) -> Self {
+            let config = Arc::new(Config::default());
+            let db = db::connect(&config).await.unwrap();
+            Self::with_router(
+                Router::new()
+                    .route("/", get(index))
+                    .route("/todo", post(add_todo))
+                    .route_layer(middleware::from_fn(state_headers))
+                    .layer(Extension(Events::new(Database::new(db, config.clone()))))
+                    .layer(middleware::from_fn(localize))
+                    .layer(Extension(config))
+                    .layer(SessionManagerLayer::new(store)),
+            )
+        }
+
+        fn with_router(app: Router) -> Self {
+            Self { app, cookie: None }
+        }
+
+        async fn send(&mut self, method: Method, uri: &str, form: &str) -> (StatusCode, String) {
+            self.send_with(method, uri, form, &[]).await
+        }
+
+        async fn send_with(
+            &mut self,
+            method: Method,
+            uri: &str,
+            form: &str,
+            headers: &[(&str, &str)],
+        ) -> (StatusCode, String) {
+            let mut request = Request::builder().method(method).uri(uri);
+            if !headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            {
+                request = request.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+            }
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            if let Some(cookie) = &self.cookie {
+                request = request.header(header::COOKIE, cookie);
+            }
+            let request = request.body(Body::from(form.to_string())).unwrap();
+
+            let response = self.app.clone().oneshot(request).await.unwrap();
+            let session_cookie = response
+                .headers()
+                .get_all(header::SET_COOKIE)
+                .iter()
+                .map(|value| value.to_str().unwrap())
+                .find(|value| value.starts_with("id="));
+            if let Some(set_cookie) = session_cookie {
+                self.cookie = set_cookie.split(';').next().map(str::to_string);
+            }
+            let status = response.status();
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+
+        /// Adds a todo and returns its id.
+        async fn add(&mut self, description: &str) -> u64 {
+            let form = format!("todo={description}&next-todo=Extend");
+            let (status, body) = self
+                .send_with(Method::POST, "/todo", &form, &[("hx-request", "true")])
+                .await;
+            assert_eq!(status, StatusCode::OK);
+
+            let start = body.find(r#"id="todo-"#).unwrap() + r#"id="todo-"#.len();
+            let end = start + body[start..].find('"').unwrap();
+            body[start..end].parse().unwrap()
+        }
+    }
+
+    /// The ids of the todos rendered in `body`, in order.
+    fn todo_ids(body: &str) -> Vec<u64> {
+        body.split(r#"id="todo-"#)
+            .skip(1)
+            .filter_map(|rest| rest[..rest.find('"').unwrap()].parse().ok())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn add_todo_renders_it() {
+        let mut client = Client::new().await;
+        let (status, body) = client
+            .send_with(
+                Method::POST,
+                "/todo",
+                "todo=buy+milk&next-todo=Extend",
+                &[("hx-request", "true")],
+            )
+            .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("buy milk"));
+        assert!(body.contains("<strong>1</strong> item left"));
+        assert!(client.cookie.is_some());
+    }
+
+    #[tokio::test]
+    async fn add_todo_outside_htmx_is_created() {
+        let app = Client::new().await.app;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/todo")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("todo=buy+milk&next-todo=Extend"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()[header::LOCATION].to_str().unwrap();
+        assert!(location.starts_with("/todo/"));
+        assert!(location["/todo/".len()..].parse::<u64>().is_ok());
+    }
+
+    #[tokio::test]
+    async fn responses_carry_the_counts_and_filter() {
+        let app = Client::new().await.app;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/todo")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header("hx-request", "true")
+            .body(Body::from("todo=buy+milk&next-todo=Extend"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        let headers = response.headers();
+        assert_eq!(headers["x-todo-active"], "1");
+        assert_eq!(headers["x-todo-completed"], "0");
+        assert_eq!(headers["x-todo-filter"], "all");
+    }
+
+    #[tokio::test]
+    async fn responses_carry_a_request_id() {
+        let app = Client::new().await.app;
+        let response = app
+            .clone()
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert!(!response.headers()["x-request-id"].is_empty());
+
+        let request = Request::builder()
+            .method(Method::PATCH)
+            .uri("/todo/1")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header("x-request-id", "abc-123")
+            .body(Body::from("desc=+"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()["x-request-id"], "abc-123");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).ends_with("(request abc-123)"));
+    }
+
+    #[tokio::test]
+    async fn theme_sets_the_pages_data_theme() {
+        let mut client = Client::new().await;
+        let (_, body) = client.send(Method::GET, "/", "").await;
+        assert!(body.contains(r#"<html lang="en" data-theme="system""#));
+
+        let (status, _) = client.send(Method::POST, "/theme", "theme=Dark").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, body) = client.send(Method::GET, "/", "").await;
+        assert!(body.contains(r#"<html lang="en" data-theme="dark""#));
+        assert!(body.contains(r#"<option value="Dark" selected>"#));
+    }
+
+    #[tokio::test]
+    async fn admin_sessions_need_the_password() {
+        let config = Config {
+            admin_password: Some("hunter2".to_string()),
+            ..Config::default()
+        };
+        let mut user = Client::with_config(config).await;
+        user.add("buy+milk").await;
+        let session_id = user.cookie.clone().unwrap()["id=".len()..].to_string();
+        let mut admin = Client::with_router(user.app.clone());
+
+        let (status, _) = admin.send(Method::GET, "/admin/sessions", "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let wrong = [("authorization", "Bearer swordfish")];
+        let (status, _) = admin
+            .send_with(Method::GET, "/admin/sessions", "", &wrong)
+            .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let auth = [("authorization", "Bearer hunter2")];
+        let (status, body) = admin
+            .send_with(Method::GET, "/admin/sessions", "", &auth)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!(r#"{{"id":"{session_id}","todos":1}}"#)));
+
+        let purge = format!("/admin/sessions/{session_id}");
+        let (status, _) = admin.send_with(Method::DELETE, &purge, "", &auth).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, body) = admin
+            .send_with(Method::GET, "/admin/sessions", "", &auth)
+            .await;
+        assert!(!body.contains(&session_id));
+    }
+
+    #[tokio::test]
+    async fn each_router_serves_its_own_config() {
+        let mut default = Client::new().await;
+        let config = Config {
+            page_size: NonZeroUsize::new(2).unwrap(),
+            ..Config::default()
+        };
+        let mut paged = Client::with_config(config).await;
+        for client in [&mut default, &mut paged] {
+            for description in ["one", "two", "three"] {
+                client.add(description).await;
+            }
+        }
+
+        let (_, body) = default.send(Method::GET, "/", "").await;
+        assert!(!body.contains("/todos/page?offset="));
+        let (_, body) = paged.send(Method::GET, "/", "").await;
+        assert!(body.contains("/todos/page?offset=2"));
+    }
+
+    #[tokio::test]
+    async fn the_new_todo_input_is_cleared_as_configured() {
+        for clear_new_todo in [true, false] {
+            let config = Config {
+                clear_new_todo,
+                ..Config::default()
+            };
+            let mut client = Client::with_config(config).await;
+
+            let (_, body) = client.send(Method::GET, "/", "").await;
+
+            assert!(body.contains(&format!(r#"data-clear-on-add="{clear_new_todo}""#)));
+            // The input is only emptied when the setting reads "true", so "false" keeps the text.
+            assert!(body.contains(
+                "$el.dataset.clearOnAdd === 'true' &amp;&amp; ($event.target.value = '')"
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn ws_commands_reply_with_the_list() {
+        let app = Client::new().await.app;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+        );
+        tokio::spawn(async move { server.await });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{address}/ws"))
+            .await
+            .unwrap();
+        let command = |text: &str| WsMessage::Text(text.to_string());
+
+        socket.send(command("not a command")).await.unwrap();
+        let reply = socket.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(reply.starts_with(r#"{"error":"#));
+
+        let add = command(r#"{"op": "add", "text": "buy milk"}"#);
+        socket.send(add).await.unwrap();
+        let reply = socket.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(reply.starts_with(r#"{"list":"#));
+        assert!(reply.contains("buy milk"));
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_refused() {
+        let mut client = Client::new().await;
+        let description = "a".repeat(Config::default().max_body_bytes);
+        let (status, _) = client
+            .send(
+                Method::POST,
+                "/todo",
+                &format!("todo={description}&next-todo=Extend"),
+            )
+            .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn duplicating_a_completed_todo_adds_an_active_copy_after_it() {
+        let mut client = Client::new().await;
+        let milk = client.add("buy+milk").await;
+        client.add("walk+dog").await;
+        client
+            .send(Method::POST, &format!("/todo/{milk}/toggle"), "")
+            .await;
+
+        let (status, body) = client
+            .send(Method::POST, &format!("/todo/{milk}/duplicate"), "")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!(r##"hx-swap-oob="afterend:#todo-{milk}""##)));
+        assert!(body.contains("<strong>2</strong> items left"));
+
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":2,"completed":1,"total":3}"#);
+        let (_, page) = client.send(Method::GET, "/", "").await;
+        let copy = page.rfind("buy milk").unwrap();
+        assert!(page.find("buy milk").unwrap() < copy);
+        assert!(copy < page.find("walk dog").unwrap());
+
+        let (status, _) = client
+            .send(Method::POST, "/todo/9999999999/duplicate", "")
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn the_calendar_counts_the_todos_due_each_day() {
+        let mut client = Client::new().await;
+        for (todo, due) in [
+            ("pay+rent", "2024-05-01"),
+            ("call+bank", "2024-05-01"),
+            ("dentist", "2024-05-14"),
+        ] {
+            client
+                .send(
+                    Method::POST,
+                    "/todo",
+                    &format!("todo={todo}&next-todo=Extend&due={due}"),
+                )
+                .await;
+        }
+
+        let (status, body) = client
+            .send(Method::GET, "/calendar?month=2024-05", "")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(
+            r#"<a href="/due/2024-05-01"><span class="day">1</span> <span class="due-count">2</span></a>"#
+        ));
+        assert!(body.contains(r#"<span class="day">14</span> <span class="due-count">1</span>"#));
+        assert!(body.contains(r#"<a href="/due/2024-05-02"><span class="day">2</span></a>"#));
+
+        // The arrows swap in just the calendar.
+        let (_, body) = client
+            .send_with(
+                Method::GET,
+                "/calendar?month=2024-06",
+                "",
+                &[("hx-request", "true")],
+            )
+            .await;
+        assert!(body.starts_with(r#"<section class="calendar" id="calendar">"#));
+
+        let (_, body) = client.send(Method::GET, "/due/2024-05-01", "").await;
+        assert!(body.contains("pay rent") && body.contains("call bank"));
+        assert!(!body.contains("dentist"));
+    }
+
+    #[tokio::test]
+    async fn completing_a_daily_todo_adds_the_next_day() {
+        let mut client = Client::new().await;
+        let (_, body) = client
+            .send_with(
+                Method::POST,
+                "/todo",
+                "todo=stretch&next-todo=Extend&due=2024-05-01&recurrence=Daily",
+                &[("hx-request", "true")],
+            )
+            .await;
+        let start = body.find(r#"id="todo-"#).unwrap() + r#"id="todo-"#.len();
+        let id = &body[start..start + body[start..].find('"').unwrap()];
+
+        let (status, body) = client
+            .send(Method::POST, &format!("/todo/{id}/toggle"), "")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("due 2024-05-01"));
+        assert!(body.contains("due 2024-05-02"));
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":1,"completed":1,"total":2}"#);
+    }
+
+    #[tokio::test]
+    async fn subtasks_count_towards_progress() {
+        let mut client = Client::new().await;
+        let id = client.add("move+house").await;
+        let add = format!("/todo/{id}/subtask");
+        client.send(Method::POST, &add, "description=pack").await;
+        let (status, body) = client
+            .send(Method::POST, &add, "description=hire+a+van")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(">0/2</span>"));
+
+        let start = body.find(&format!("/todo/{id}/subtask/")).unwrap();
+        let end = start + body[start..].find('"').unwrap();
+        let (status, body) = client
+            .send(Method::PATCH, &body[start..end], "completed=true")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(">1/2</span>"));
+        assert!(
+            body.contains(r#"<li class="completed"><input class="toggle" type="checkbox" checked"#)
+        );
+        // The todo itself stays active while a subtask is left.
+        assert!(!body.contains(r#"<li class="completed" id="todo-"#));
+    }
+
+    /// A client deleting under `policy`, holding another todo and then a parent of two subtasks,
+    /// whose id is returned.
+    async fn parent_of_two_subtasks(policy: SubtaskPolicy) -> (Client, u64) {
+        let mut client = Client::with_config(Config {
+            subtask_delete_policy: policy,
+            ..Config::default()
+        })
+        .await;
+        client.add("walk+dog").await;
+        let id = client.add("move+house").await;
+        let add = format!("/todo/{id}/subtask");
+        client.send(Method::POST, &add, "description=pack").await;
+        client
+            .send(Method::POST, &add, "description=hire+a+van")
+            .await;
+        (client, id)
+    }
+
+    #[tokio::test]
+    async fn deleting_a_parent_can_delete_its_subtasks() {
+        let (mut client, id) = parent_of_two_subtasks(SubtaskPolicy::Delete).await;
+
+        let (status, _) = client
+            .send(Method::DELETE, &format!("/todo/{id}"), "")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = client.send(Method::GET, "/", "").await;
+        assert_eq!(todo_ids(&body).len(), 1);
+        assert!(!body.contains("hire a van"));
+    }
+
+    #[tokio::test]
+    async fn deleting_a_parent_can_promote_its_subtasks() {
+        let (mut client, id) = parent_of_two_subtasks(SubtaskPolicy::Promote).await;
+
+        let (status, body) = client
+            .send(Method::DELETE, &format!("/todo/{id}"), "")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let ids = todo_ids(&body);
+        assert_eq!(ids.len(), 3);
+        assert!(!ids.contains(&id));
+        let promoted = body.find("pack").unwrap();
+        assert!(body.find("walk dog").unwrap() < promoted);
+        assert!(promoted < body.find("hire a van").unwrap());
+    }
+
+    #[tokio::test]
+    async fn deleting_a_parent_can_be_blocked() {
+        let (mut client, id) = parent_of_two_subtasks(SubtaskPolicy::Block).await;
+
+        let (status, body) = client
+            .send(Method::DELETE, &format!("/todo/{id}"), "")
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.starts_with("the todo has subtasks, delete them first"));
+        let (_, body) = client.send(Method::GET, "/", "").await;
+        assert_eq!(todo_ids(&body).len(), 2);
+        assert!(body.contains(r#"<button class="destroy" disabled"#));
+    }
+
+    #[tokio::test]
+    async fn deleting_a_parent_prompts_by_default() {
+        let (mut client, id) = parent_of_two_subtasks(SubtaskPolicy::default()).await;
+        let uri = format!("/todo/{id}");
+
+        let (_, body) = client.send(Method::GET, "/", "").await;
+        assert!(body.contains(&format!(r#"hx-delete="{uri}?subtasks=promote""#)));
+        let (status, _) = client.send(Method::DELETE, &uri, "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = client
+            .send(Method::DELETE, &format!("{uri}?subtasks=delete"), "")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = client.send(Method::GET, "/", "").await;
+        assert_eq!(todo_ids(&body).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn new_todo_placeholder_is_one_of_the_examples() {
+        let mut client = Client::new().await;
+        let (_, body) = client.send(Method::GET, "/", "").await;
+
+        let start = body.find(r#"placeholder=""#).unwrap() + r#"placeholder=""#.len();
+        let end = start + body[start..].find('"').unwrap();
+        assert!(crate::i18n::ENGLISH
+            .placeholders
+            .contains(&&body[start..end]));
+    }
+
+    #[tokio::test]
+    async fn spanish_is_served_when_asked_for() {
+        let mut client = Client::new().await;
+        client.add("comprar+leche").await;
+        let (_, body) = client
+            .send_with(
+                Method::GET,
+                "/",
+                "",
+                &[("accept-language", "es-ES,es;q=0.9")],
+            )
+            .await;
+
+        assert!(body.contains(r#"<html lang="es""#));
+        assert!(body.contains("<h1>tareas</h1>"));
+        assert!(body.contains("tarea pendiente"));
+        assert!(body.contains("Haz doble clic para editar una tarea"));
+        assert!(body.contains(r#"placeholder="Añadir una subtarea""#));
+        assert!(!body.contains("item left"));
+    }
+
+    #[tokio::test]
+    async fn favicon_shows_the_active_count() {
+        let mut client = Client::new().await;
+        client.add("buy+milk").await;
+        client.add("walk+dog").await;
+
+        let (status, body) = client.send(Method::GET, "/favicon.svg", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with("<svg"));
+        assert!(body.contains(">2</text>"));
+    }
+
+    #[tokio::test]
+    async fn quick_add_redirects_home() {
+        let mut client = Client::new().await;
+        let (status, _) = client
+            .send_with(
+                Method::GET,
+                "/add?text=buy%20milk",
+                "",
+                &[("sec-fetch-site", "none")],
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        let (_, body) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(body, r#"{"active":1,"completed":0,"total":1}"#);
+    }
+
+    #[tokio::test]
+    async fn quick_add_refuses_other_sites() {
+        let mut client = Client::new().await;
+        let (status, _) = client
+            .send_with(
+                Method::GET,
+                "/add?text=buy%20milk",
+                "",
+                &[("sec-fetch-site", "cross-site")],
+            )
+            .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (_, body) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(body, r#"{"active":0,"completed":0,"total":0}"#);
+    }
+
+    #[tokio::test]
+    async fn add_todo_at_the_top() {
+        let mut client = Client::new().await;
+        let first = client.add("buy+milk").await;
+        let (status, _) = client
+            .send(Method::POST, "/add-position", "add_position=Top")
+            .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, body) = client
+            .send_with(
+                Method::POST,
+                "/todo",
+                "todo=walk+dog&next-todo=Extend",
+                &[("hx-request", "true")],
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let walk_dog = body.find("walk dog").unwrap();
+        let buy_milk = body.find(&format!(r#"id="todo-{first}""#)).unwrap();
+        assert!(walk_dog < buy_milk);
+    }
+
+    #[tokio::test]
+    async fn snapshot_view_is_read_only() {
+        let mut client = Client::new().await;
+        client.add("buy+milk").await;
+        let (status, _) = client.send(Method::POST, "/snapshot", "name=week+1").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, body) = client
+            .send(Method::GET, "/snapshots/week%201/view", "")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("buy milk"));
+        assert!(!body.contains("hx-patch"));
+        assert!(!body.contains("hx-delete"));
+
+        let (status, _) = client
+            .send(Method::GET, "/snapshots/missing/view", "")
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn tag_matching_tags_every_todo_containing_the_search() {
+        let mut client = Client::new().await;
+        let milk = client.add("buy+milk").await;
+        let oat_milk = client.add("oat+MILK+%23shopping").await;
+        let dog = client.add("walk+dog").await;
+
+        let (status, body) = client
+            .send(Method::POST, "/tag-matching", "q=milk&tag=%23Shopping")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#"id="todo-list""#));
+        // The oat milk was already tagged, so only the one todo gained the tag.
+        assert!(body.contains("Todo edited, 3 items left"));
+
+        for (id, tagged) in [(milk, true), (oat_milk, true), (dog, false)] {
+            let (_, body) = client.send(Method::GET, &format!("/todo/{id}"), "").await;
+            let tags = body.matches("#shopping").count();
+            assert_eq!(tags, usize::from(tagged), "todo {id}");
+        }
+    }
+
+    #[tokio::test]
+    async fn filtering_by_tags_in_and_mode_lists_todos_with_every_tag() {
+        let mut client = Client::new().await;
+        let both = client.add("plan+trip+%23travel+%23work").await;
+        let travel = client.add("pack+%23travel").await;
+        let work = client.add("report+%23work").await;
+        let done = client.add("book+flights+%23travel+%23work").await;
+        client
+            .send(Method::POST, &format!("/todo/{done}/toggle"), "")
+            .await;
+        client.send(Method::POST, "/select", "filter=Active").await;
+
+        let (status, body) = client
+            .send(Method::GET, "/filter/tags?tags=travel,%23Work&mode=and", "")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!(r#"id="todo-{both}""#)));
+        for id in [travel, work, done] {
+            assert!(!body.contains(&format!(r#"id="todo-{id}""#)));
+        }
+
+        // The filter is kept for the next render of the list.
+        let (_, body) = client.send(Method::GET, "/", "").await;
+        assert!(!body.contains(&format!(r#"id="todo-{travel}""#)));
+    }
+
+    #[tokio::test]
+    async fn search_lists_only_matching_todos() {
+        let mut client = Client::new().await;
+        let milk = client.add("buy+milk").await;
+        let dog = client.add("walk+dog").await;
+
+        let (status, body) = client.send(Method::POST, "/search", "q=MILK").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!(r#"id="todo-{milk}""#)));
+        assert!(body.contains("<mark>milk</mark>"));
+        assert!(!body.contains(&format!(r#"id="todo-{dog}""#)));
+
+        let (_, body) = client.send(Method::POST, "/search", "q=").await;
+        assert!(body.contains(&format!(r#"id="todo-{dog}""#)));
+    }
+
+    #[tokio::test]
+    async fn session_ttl_counts_down_until_keepalive() {
+        let mut client = Client::new().await;
+        client.add("buy+milk").await;
+        let full = i64::from(Config::default().session_ttl_minutes.get()) * 60;
+        let seconds = |body: &str| -> i64 {
+            body.trim_start_matches(r#"{"seconds":"#)
+                .trim_end_matches('}')
+                .parse()
+                .unwrap()
+        };
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let (status, body) = client.send(Method::GET, "/api/session-ttl", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(seconds(&body) < full);
+
+        let (status, _) = client.send(Method::POST, "/keepalive", "").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, body) = client.send(Method::GET, "/api/session-ttl", "").await;
+        assert!(seconds(&body) >= full - 1);
+    }
+
+    #[tokio::test]
+    async fn adding_too_fast_is_rate_limited() {
+        let mut client = Client::new().await;
+        let burst = Config::default().rate_limit_burst.get();
+
+        let mut statuses = Vec::new();
+        for _ in 0..burst * 2 {
+            let (status, _) = client
+                .send(Method::POST, "/todo", "todo=spam&next-todo=Extend")
+                .await;
+            statuses.push(status);
+        }
+        assert!(statuses.contains(&StatusCode::TOO_MANY_REQUESTS));
+
+        let (status, _) = client.send(Method::GET, "/", "").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn made_up_session_cookies_share_a_rate_limit() {
+        let mut client = Client::new().await;
+        let burst = Config::default().rate_limit_burst.get();
+
+        let mut statuses = Vec::new();
+        for _ in 0..burst * 2 {
+            client.cookie = Some(format!("id={}", tower_sessions::session::Id::default()));
+            let (status, _) = client
+                .send(Method::POST, "/todo", "todo=spam&next-todo=Extend")
+                .await;
+            statuses.push(status);
+        }
+        assert!(statuses.contains(&StatusCode::TOO_MANY_REQUESTS));
+    }
+
+    #[tokio::test]
+    async fn a_template_can_be_instantiated_twice() {
+        let mut client = Client::new().await;
+        let id = client.add("water+plants").await;
+        let (status, _) = client
+            .send(Method::POST, &format!("/todo/{id}/make-template"), "")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let instantiate = format!("/template/{id}/instantiate");
+        client.send(Method::POST, &instantiate, "").await;
+        let (status, body) = client.send(Method::POST, &instantiate, "").await;
+        assert_eq!(status, StatusCode::OK);
+        let ids = todo_ids(&body);
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+        assert!(!ids.contains(&id));
+    }
+
+    /// A session store whose loads see every save but the latest, like a replica that is one
+    /// write behind.
+    #[derive(Clone, Debug, Default)]
+    struct LaggingStore {
+        saves: Arc<Mutex<HashMap<SessionId, Vec<Record>>>>,
+    }
+
+    #[async_trait]
+    impl SessionStore for LaggingStore {
+        async fn save(&self, record: &Record) -> session_store::Result<()> {
+            let mut saves = self.saves.lock().unwrap();
+            saves.entry(record.id).or_default().push(record.clone());
+            Ok(())
+        }
+
+        async fn load(&self, id: &SessionId) -> session_store::Result<Option<Record>> {
+            let saves = self.saves.lock().unwrap();
+            let Some(saves) = saves.get(id) else {
+                return Ok(None);
+            };
+            Ok(saves.iter().rev().nth(1).or(saves.last()).cloned())
+        }
+
+        async fn delete(&self, id: &SessionId) -> session_store::Result<()> {
+            self.saves.lock().unwrap().remove(id);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn a_stale_store_is_reported_by_the_state_version() {
+        let mut client = Client::with_store(LaggingStore::default()).await;
+        client.add("first").await;
+        let cookie = client.cookie.clone().unwrap();
+
+        let version = |response: &Response| -> u64 {
+            let value = response.headers()[STATE_VERSION_HEADER].to_str().unwrap();
+            value.parse().unwrap()
+        };
+        let add = Request::post("/todo")
+            .header(header::COOKIE, &cookie)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("todo=walk+the+dog&next-todo=Extend"))
+            .unwrap();
+        let written = version(&client.app.clone().oneshot(add).await.unwrap());
+        let index = Request::get("/")
+            .header(header::COOKIE, &cookie)
+            .body(Body::empty())
+            .unwrap();
+        let page = client.app.clone().oneshot(index).await.unwrap();
+
+        assert!(version(&page) < written);
+        let body = to_bytes(page.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(&format!(r#"data-state-version="{}""#, written - 1)));
+        assert!(!body.contains("walk the dog"));
+    }
+
+    #[tokio::test]
+    async fn static_assets_do_not_report_the_state() {
+        let mut client = Client::new().await;
+        client.add("buy+milk").await;
+
+        for uri in ["/style.css", "/favicon.svg"] {
+            let request = Request::get(uri)
+                .header(header::COOKIE, client.cookie.as_deref().unwrap())
+                .body(Body::empty())
+                .unwrap();
+            let response = client.app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(!response.headers().contains_key(STATE_VERSION_HEADER));
+        }
+    }
+
+    #[tokio::test]
+    async fn panicking_handlers_answer_500() {
+        async fn panics() -> StatusCode {
+            panic!("boom")
+        }
+        let app = Router::new()
+            .route("/panic", get(panics))
+            .layer(CatchPanicLayer::custom(handle_panic));
+        let mut client = Client::with_router(app);
+
+        let (status, body) = client.send(Method::GET, "/panic", "").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body, "internal server error");
+    }
+
+    #[tokio::test]
+    async fn pages_are_gzipped_for_clients_that_accept_it() {
+        let client = Client::new().await;
+        let request = Request::get("/")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = client.app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    }
+
+    #[tokio::test]
+    async fn delete_todos_deletes_just_the_listed_ones() {
+        let mut client = Client::new().await;
+        let first = client.add("first").await;
+        let second = client.add("second").await;
+        let third = client.add("third").await;
+
+        let form = format!("ids%5B%5D={first}&ids%5B%5D={third}");
+        let (status, body) = client.send(Method::POST, "/todos/delete", &form).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(todo_ids(&body), [second]);
+    }
+
+    #[tokio::test]
+    async fn delete_todos_rejects_malformed_ids() {
+        let mut client = Client::new().await;
+        let id = client.add("keep+me").await;
+
+        let (status, _) = client
+            .send(Method::POST, "/todos/delete", &format!("ids={id}&ids=x"))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let json = [("content-type", "application/json")];
+        let (status, _) = client
+            .send_with(Method::POST, "/todos/delete", r#"{"ids": ["x"]}"#, &json)
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (_, body) = client.send(Method::GET, &format!("/todo/{id}"), "").await;
+        assert_eq!(todo_ids(&body), [id]);
+    }
+
+    #[tokio::test]
+    async fn over_long_descriptions_are_rejected() {
+        let mut client = Client::new().await;
+        let too_long = "a".repeat(Todo::MAX_DESCRIPTION_LENGTH + 1);
+
+        let (status, body) = client
+            .send(
+                Method::POST,
+                "/todo",
+                &format!("todo={too_long}&next-todo=Extend"),
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("at most"));
+
+        let id = client.add("buy+milk").await;
+        let uri = format!("/todo/{id}");
+        let (status, _) = client
+            .send(Method::PATCH, &uri, &format!("desc={too_long}"))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, body) = client.send(Method::GET, &uri, "").await;
+        assert!(body.contains("buy milk"));
+    }
+
+    #[tokio::test]
+    async fn slow_handlers_time_out() {
+        async fn slow() -> StatusCode {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            StatusCode::OK
+        }
+        let app = Router::new()
+            .route("/slow", get(slow))
+            .layer(TimeoutLayer::new(Duration::from_millis(50)));
+        let mut client = Client::with_router(app);
+
+        let (status, _) = client.send(Method::GET, "/slow", "").await;
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn snapshots_keep_the_list_as_it_was() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+        let (status, _) = client.send(Method::POST, "/snapshot", "name=before").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        client
+            .send(Method::PATCH, &format!("/todo/{id}"), "desc=buy+oat+milk")
+            .await;
+        client
+            .send(Method::POST, &format!("/todo/{id}/toggle"), "")
+            .await;
+        client.add("walk+dog").await;
+
+        let (status, body) = client.send(Method::GET, "/snapshots/before", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let todos: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let todos = todos.as_array().unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0]["description"], "buy milk");
+        assert_eq!(todos[0]["completed"], false);
+
+        let (status, _) = client.send(Method::GET, "/snapshots/before", "").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn one_add_records_one_event() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+
+        let (status, body) = client.send(Method::GET, "/events", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let events: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let events = events.as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["action"], "Added");
+        assert_eq!(events[0]["todo_id"], id);
+    }
+
+    #[tokio::test]
+    async fn malformed_todo_ids_are_bad_requests() {
+        let mut client = Client::new().await;
+
+        for method in [Method::GET, Method::DELETE] {
+            let (status, body) = client.send(method, "/todo/not-a-number", "").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(body.starts_with("id must be a positive integer"));
+        }
+    }
+
+    #[tokio::test]
+    async fn toggle_batch_sets_several_todos_at_once() {
+        let mut client = Client::new().await;
+        let first = client.add("first").await;
+        let second = client.add("second").await;
+        let third = client.add("third").await;
+
+        let changes = format!(
+            r#"[{{"id":{first},"completed":true}},{{"id":{second},"completed":true}},{{"id":{third},"completed":false}},{{"id":999,"completed":true}}]"#
+        );
+        let json = [("content-type", "application/json")];
+        let (status, body) = client
+            .send_with(Method::POST, "/api/toggle-batch", &changes, &json)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let batch: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let completed: Vec<_> = batch["todos"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|todo| {
+                (
+                    todo["id"].as_u64().unwrap(),
+                    todo["completed"].as_bool().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(completed, [(first, true), (second, true), (third, false)]);
+        assert_eq!(batch["unknown"], serde_json::json!([999]));
+
+        let (_, body) = client.send(Method::GET, "/count", "").await;
+        assert!(body.contains(r#""active":1"#));
+    }
+
+    #[tokio::test]
+    async fn adds_past_the_cap_are_refused() {
+        let config = Config {
+            max_todos: 2,
+            ..Config::default()
+        };
+        let mut client = Client::with_config(config).await;
+        client.add("first").await;
+        client.add("second").await;
+
+        let (status, body) = client
+            .send(Method::POST, "/todo", "todo=third&next-todo=Extend")
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.starts_with("a list may hold at most 2 todos"));
+
+        let (_, body) = client.send(Method::GET, "/count", "").await;
+        assert!(body.contains(r#""total":2"#));
+    }
+
+    #[tokio::test]
+    async fn clearing_every_todo_renders_the_empty_state() {
+        let mut client = Client::new().await;
+        client.add("first").await;
+        client.add("second").await;
+
+        let (status, body) = client.send(Method::DELETE, "/todos", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            r#"<input id="todo-list" hx-swap-oob="true" type="hidden" name="next-todo" value="FullPayload">"#
+        );
+    }
+
+    #[tokio::test]
+    async fn admin_config_shows_the_configured_page_size() {
+        let config = Config {
+            admin_password: Some("hunter2".to_string()),
+            page_size: NonZeroUsize::new(7).unwrap(),
+            ..Config::default()
+        };
+        let mut admin = Client::with_config(config).await;
+
+        let auth = [("authorization", "Bearer hunter2")];
+        let (status, body) = admin
+            .send_with(Method::GET, "/admin/config", "", &auth)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let config: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(config["page_size"], 7);
+        assert!(!body.contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn merging_a_session_brings_over_every_list() {
+        let mut client = Client::new().await;
+        let mut other = Client::with_router(client.app.clone());
+        other.add("their+errand").await;
+        other.send(Method::POST, "/list", "name=Work").await;
+        other.add("their+report").await;
+        let other_id = other.cookie.clone().unwrap()["id=".len()..].to_string();
+        client.add("my+errand").await;
+
+        let (status, body) = client
+            .send(
+                Method::POST,
+                "/merge-session",
+                &format!("session={other_id}"),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("my errand"));
+        assert!(body.contains("their errand"));
+        assert!(body.contains("Work"));
+        assert!(!body.contains("their report"));
+        assert_eq!(todo_ids(&body).len(), 2);
+
+        let (_, body) = client.send(Method::POST, "/list", "name=Work").await;
+        assert!(body.contains("their report"));
+
+        let (status, _) = client
+            .send(
+                Method::POST,
+                "/merge-session",
+                &format!("session={other_id}"),
+            )
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn stale_lists_untouched_active_todos_oldest_first() {
+        let mut client = Client::new().await;
+        let first = client.add("first").await;
+        let second = client.add("second").await;
+        let third = client.add("third").await;
+        client
+            .send(Method::POST, &format!("/todo/{second}/toggle"), "")
+            .await;
+        client
+            .send(Method::POST, &format!("/todo/{first}/touch"), "")
+            .await;
+
+        let (status, body) = client.send(Method::GET, "/stale", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body.contains("<li"));
+
+        let (_, body) = client.send(Method::GET, "/stale?days=0", "").await;
+        let stale: Vec<u64> = body
+            .split(r#"id="stale-"#)
+            .skip(1)
+            .map(|rest| rest[..rest.find('"').unwrap()].parse().unwrap())
+            .collect();
+        assert_eq!(stale, [third, first]);
+    }
+
+    #[tokio::test]
+    async fn completing_the_last_todo_celebrates_when_enabled() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+        let toggle = |client: &Client| {
+            Request::post(format!("/todo/{id}/toggle"))
+                .header(header::COOKIE, client.cookie.as_deref().unwrap())
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = client.app.clone().oneshot(toggle(&client)).await.unwrap();
+        assert!(!response.headers().contains_key("hx-trigger"));
+        client
+            .send(Method::POST, "/celebration", "celebration=Confetti")
+            .await;
+        let response = client.app.clone().oneshot(toggle(&client)).await.unwrap();
+        assert!(!response.headers().contains_key("hx-trigger"));
+
+        let response = client.app.clone().oneshot(toggle(&client)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["hx-trigger"], "celebrate");
+    }
+
+    #[tokio::test]
+    async fn split_makes_a_todo_of_each_part() {
+        let mut client = Client::new().await;
+        let id = client.add("milk%3B+eggs%3B+%3B+bread").await;
+
+        let (status, body) = client
+            .send(Method::POST, &format!("/todo/{id}/split"), "delimiter=%3B")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let ids = todo_ids(&body);
+        assert_eq!(ids.len(), 3);
+        assert!(!ids.contains(&id));
+        let milk = body.find(">milk<").unwrap();
+        let eggs = body.find(">eggs<").unwrap();
+        let bread = body.find(">bread<").unwrap();
+        assert!(milk < eggs && eggs < bread);
+    }
+
+    #[tokio::test]
+    async fn split_into_nothing_keeps_the_todo() {
+        let mut client = Client::new().await;
+        let id = client.add("%3B+%3B").await;
+
+        let (status, _) = client
+            .send(Method::POST, &format!("/todo/{id}/split"), "delimiter=%3B")
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, body) = client.send(Method::GET, "/", "").await;
+        assert_eq!(todo_ids(&body), [id]);
+    }
+
+    #[tokio::test]
+    async fn export_csv_escapes_descriptions() {
+        let mut client = Client::new().await;
+        let id = client.add("milk%2C+eggs").await;
+
+        let (status, body) = client.send(Method::GET, "/export.csv", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#""milk, eggs""#));
+
+        let mut reader = csv::Reader::from_reader(body.as_bytes());
+        let headers = reader.headers().unwrap().clone();
+        assert_eq!(&headers[0], "id");
+        assert_eq!(&headers[1], "description");
+        let rows: Vec<_> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0][0].parse::<u64>().unwrap(), id);
+        assert_eq!(&rows[0][1], "milk, eggs");
+        assert_eq!(&rows[0][2], "false");
+    }
+
+    #[tokio::test]
+    async fn index_is_not_modified_until_the_state_changes() {
+        let app = Client::new().await.app;
+        let get = |etag: Option<&str>| {
+            let mut request = Request::builder().uri("/");
+            if let Some(etag) = etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = get(None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let response = get(Some(&etag)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+
+        let response = get(Some(r#""stale""#)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn duplicate_todos_are_refused_unless_forced() {
+        let mut client = Client::new().await;
+        client.add("buy+milk").await;
+
+        let (status, body) = client
+            .send(Method::POST, "/todo", "todo=+Buy+Milk+&next-todo=Extend")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("is already on your list"));
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":1,"completed":0,"total":1}"#);
+
+        client.add("buy+milk&force=true").await;
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":2,"completed":0,"total":2}"#);
+    }
+
+    #[tokio::test]
+    async fn selected_filter_is_kept_in_a_cookie() {
+        let app = Client::new().await.app;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/select")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("filter=Active"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let filter_cookie = response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .find(|value| value.starts_with("filter="))
+            .unwrap();
+        assert!(filter_cookie.starts_with("filter=Active;"));
+
+        // A new session starts from the cookie.
+        let mut client = Client::new().await;
+        client.cookie = Some("filter=Active".to_string());
+        let (status, _) = client.send(Method::GET, "/", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = client
+            .send_with(
+                Method::POST,
+                "/todo",
+                "todo=buy+milk&next-todo=Extend",
+                &[("hx-request", "true")],
+            )
+            .await;
+        assert!(body.contains(r#"class="selected" href="/active" hx-post="/select">Active</a>"#));
+    }
+
+    #[tokio::test]
+    async fn filter_pages_render_without_saving_the_filter() {
+        let mut client = Client::new().await;
+        client.add("buy+milk").await;
+
+        let (status, body) = client.send(Method::GET, "/completed", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#"class="selected" href="/completed""#));
+        assert!(!body.contains("buy milk"));
+
+        let (_, body) = client.send(Method::GET, "/", "").await;
+        assert!(body.contains(r#"class="selected" href="/""#));
+        assert!(body.contains("buy milk"));
+    }
+
+    #[tokio::test]
+    async fn add_todo_with_a_repeated_idempotency_key_adds_once() {
+        let mut client = Client::new().await;
+        let headers = [("hx-request", "true"), ("idempotency-key", "abc123")];
+        let form = "todo=buy+milk&next-todo=Extend";
+
+        let (status, first) = client
+            .send_with(Method::POST, "/todo", form, &headers)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, retry) = client
+            .send_with(Method::POST, "/todo", form, &headers)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(first, retry);
+
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":1,"completed":0,"total":1}"#);
+
+        let headers = [("hx-request", "true"), ("idempotency-key", "def456")];
+        client
+            .send_with(
+                Method::POST,
+                "/todo",
+                "todo=walk+dog&next-todo=Extend",
+                &headers,
+            )
+            .await;
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":2,"completed":0,"total":2}"#);
+    }
+
+    #[tokio::test]
+    async fn get_todo_renders_just_that_todo() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+        client.add("walk+dog").await;
+
+        let (status, body) = client.send(Method::GET, &format!("/todo/{id}"), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with("<li"));
+        assert!(body.contains(&format!(r#"id="todo-{id}""#)));
+        assert!(body.contains("buy milk"));
+        assert!(body.contains("just now"));
+        assert!(!body.contains("walk dog"));
+
+        let (status, _) = client.send(Method::GET, "/todo/9999999999", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn cancelling_an_edit_gets_the_saved_description() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+        let uri = format!("/todo/{id}");
+        client.send(Method::PATCH, &uri, "desc=buy+oat+milk").await;
+
+        let (status, body) = client.send(Method::GET, &uri, "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("buy oat milk"));
+        assert!(!body.contains("buy milk"));
+    }
+
+    #[tokio::test]
+    async fn toggle_todo_completes_it() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+        let uri = format!("/todo/{id}/toggle");
+
+        let (status, body) = client.send(Method::POST, &uri, "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!(r#"class="completed" id="todo-{id}""#)));
+        assert!(body.contains("<strong>0</strong> items left"));
+        assert!(body.contains("done just now"));
+
+        let (status, body) = client.send(Method::POST, &uri, "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body.contains(r#"class="completed""#));
+        assert!(body.contains("<strong>1</strong> item left"));
+        assert!(!body.contains("done just now"));
+    }
+
+    #[tokio::test]
+    async fn editing_a_description_leaves_the_footer_alone() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+
+        let (status, body) = client
+            .send(Method::PATCH, &format!("/todo/{id}"), "desc=buy+oat+milk")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("buy oat milk"));
+        assert!(!body.contains(r#"id="footer""#));
+        assert!(!body.contains("todo-count"));
+    }
+
+    #[tokio::test]
+    async fn json_patch_completes_and_renames_at_once() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+
+        let (status, body) = client
+            .send_with(
+                Method::PATCH,
+                &format!("/todo/{id}"),
+                r#"{"completed": true, "desc": "buy oat milk"}"#,
+                &[("content-type", "application/json")],
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!(r#"class="completed" id="todo-{id}""#)));
+        assert!(body.contains("buy oat milk"));
+        assert!(body.contains("<strong>0</strong> items left"));
+
+        // Sending the same value again leaves it be rather than flipping it.
+        client
+            .send_with(
+                Method::PATCH,
+                &format!("/todo/{id}"),
+                r#"{"completed": true}"#,
+                &[("content-type", "application/json")],
+            )
+            .await;
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":0,"completed":1,"total":1}"#);
+    }
+
+    #[tokio::test]
+    async fn export_csv_filters_by_completion() {
+        let mut client = Client::new().await;
+        let done = client.add("buy+milk").await;
+        client.add("walk+dog").await;
+        client
+            .send(Method::POST, &format!("/todo/{done}/toggle"), "")
+            .await;
+
+        let (_, body) = client
+            .send(Method::GET, "/export.csv?filter=active", "")
+            .await;
+        assert!(body.contains("walk dog"));
+        assert!(!body.contains("buy milk"));
+
+        let (_, body) = client
+            .send(Method::GET, "/export.csv?filter=completed", "")
+            .await;
+        assert!(body.contains("buy milk"));
+        assert!(!body.contains("walk dog"));
+
+        let (status, _) = client
+            .send(Method::GET, "/export.csv?filter=someday", "")
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn batch_applies_each_operation_in_order() {
+        let mut client = Client::new().await;
+        let milk = client.add("buy+milk").await;
+        let dog = client.add("walk+dog").await;
+        let json = [("content-type", "application/json")];
+
+        let ops = format!(
+            r#"[{{"op": "add", "text": "water plants"}}, {{"op": "delete", "id": {milk}}},
+                {{"op": "toggle", "id": {dog}}}, {{"op": "toggle", "id": 9999999999}}]"#
+        );
+        let (status, body) = client.send_with(Method::POST, "/batch", &ops, &json).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!(r#"{{"id":{milk}}}"#)));
+        assert!(body.contains(r#"{"error":"no todo with id 9999999999"}"#));
+        assert!(body.contains("water plants"));
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":1,"completed":1,"total":2}"#);
+
+        let ops = format!(r#"[{{"op": "toggle", "id": {dog}}}, {{"op": "delete", "id": {milk}}}]"#);
+        let (status, _) = client
+            .send_with(Method::POST, "/batch?atomic=true", &ops, &json)
+            .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":1,"completed":1,"total":2}"#);
+    }
+
+    #[tokio::test]
+    async fn capacity_banner_shows_from_ninety_percent() {
+        let mut client = Client::new().await;
+        let ops = vec![r#"{"op": "add", "text": "filler"}"#; 898].join(",");
+        let json = [("content-type", "application/json")];
+        let (status, _) = client
+            .send_with(Method::POST, "/batch", &format!("[{ops}]"), &json)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let below = client.add("below").await;
+        let (_, body) = client.send(Method::GET, "/", "").await;
+        assert!(body.contains(r#"id="capacity-banner""#));
+        assert!(!body.contains("near the 1000-todo limit"));
+
+        let (_, body) = client
+            .send_with(
+                Method::POST,
+                "/todo",
+                "todo=at+the+threshold&next-todo=Extend",
+                &[("hx-request", "true")],
+            )
+            .await;
+        assert!(body.contains("You're near the 1000-todo limit"));
+
+        let (_, body) = client
+            .send(Method::DELETE, &format!("/todo/{below}"), "")
+            .await;
+        assert!(body.contains(r#"id="capacity-banner""#));
+        assert!(!body.contains("near the 1000-todo limit"));
+    }
+
+    #[tokio::test]
+    async fn patch_todo_refuses_a_stale_version() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+        let uri = format!("/todo/{id}");
+
+        let (status, _) = client
+            .send(Method::PATCH, &uri, "desc=buy+milk&version=0")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = client
+            .send(Method::PATCH, &uri, "desc=buy+oat+milk&version=0")
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn a_conflict_shows_what_changed_since_the_page_was_rendered() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+        let uri = format!("/todo/{id}");
+        let (_, page) = client.send(Method::GET, "/", "").await;
+        let seen = page
+            .split_once(r#"data-state-version=""#)
+            .and_then(|(_, rest)| rest.split_once('"'))
+            .map(|(version, _)| version.to_string())
+            .unwrap();
+
+        // Changed elsewhere after the page was rendered.
+        client
+            .send(Method::PATCH, &uri, "desc=buy+oat+milk&version=0")
+            .await;
+        client.add("walk+dog").await;
+
+        let (status, body) = client
+            .send_with(
+                Method::PATCH,
+                &uri,
+                "desc=buy+soy+milk&version=0",
+                &[("x-state-version", &seen)],
+            )
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.contains(r#"<tr class="added"><td></td><td>walk dog</td></tr>"#));
+        assert!(body.contains(r#"<tr class="changed"><td>buy milk</td><td>buy oat milk</td></tr>"#));
+    }
+
+    #[tokio::test]
+    async fn changes_are_announced() {
+        let mut client = Client::new().await;
+        let (_, body) = client
+            .send_with(
+                Method::POST,
+                "/todo",
+                "todo=buy+milk&next-todo=Extend",
+                &[("hx-request", "true")],
+            )
+            .await;
+        assert!(body.contains("Todo added, 1 item left"));
+
+        let id = client.add("walk+dog").await;
+        let (_, body) = client
+            .send(Method::DELETE, &format!("/todo/{id}"), "")
+            .await;
+        assert!(body.contains(r#"id="announcer""#));
+        assert!(body.contains("Todo deleted, 1 item left"));
+    }
+
+    #[tokio::test]
+    async fn delete_todo_updates_the_footer() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+        client.add("walk+dog").await;
+
+        let (status, body) = client
+            .send(Method::DELETE, &format!("/todo/{id}"), "")
+            .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<strong>1</strong> item left"));
+        assert!(body.contains(r#"id="footer" hx-swap-oob="true""#));
+        assert!(body.contains(&format!(r#"<li id="todo-{id}" hx-swap-oob="delete">"#)));
+    }
+
+    #[tokio::test]
+    async fn undo_brings_back_a_deleted_todo() {
+        let mut client = Client::new().await;
+        let id = client.add("buy+milk").await;
+        client
+            .send(Method::DELETE, &format!("/todo/{id}"), "")
+            .await;
+
+        let (status, body) = client.send(Method::POST, "/undo", "").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!(r#"id="todo-{id}""#)));
+    }
+
+    #[tokio::test]
+    async fn toggle_todos_completes_them_all() {
+        let mut client = Client::new().await;
+        let first = client.add("buy+milk").await;
+        let second = client.add("walk+dog").await;
+
+        let (status, body) = client.send(Method::POST, "/toggle-todos", "").await;
+
+        assert_eq!(status, StatusCode::OK);
+        for id in [first, second] {
+            assert!(body.contains(&format!(r#"class="completed" id="todo-{id}""#)));
+        }
+    }
+
+    #[tokio::test]
+    async fn toggle_todos_completes_only_active_ones_shown() {
+        let mut client = Client::new().await;
+        let done = client.add("buy+milk").await;
+        client.add("walk+dog").await;
+        client
+            .send(Method::POST, &format!("/todo/{done}/toggle"), "")
+            .await;
+        client.send(Method::POST, "/select", "filter=Active").await;
+
+        let (status, _) = client.send(Method::POST, "/toggle-todos", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":0,"completed":2,"total":2}"#);
+
+        // Nothing active is shown any more, so toggling again leaves the hidden todos be.
+        client.send(Method::POST, "/toggle-todos", "").await;
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":0,"completed":2,"total":2}"#);
+    }
+
+    #[tokio::test]
+    async fn toggle_todos_reopens_only_completed_ones_shown() {
+        let mut client = Client::new().await;
+        let done = client.add("buy+milk").await;
+        client.add("walk+dog").await;
+        client
+            .send(Method::POST, &format!("/todo/{done}/toggle"), "")
+            .await;
+        client
+            .send(Method::POST, "/select", "filter=Completed")
+            .await;
+
+        let (status, _) = client.send(Method::POST, "/toggle-todos", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, count) = client.send(Method::GET, "/count", "").await;
+        assert_eq!(count, r#"{"active":2,"completed":0,"total":2}"#);
+    }
+
+    #[tokio::test]
+    async fn toggle_todos_delta_has_only_the_changed_todo() {
+        let mut client = Client::new().await;
+        let done = client.add("buy+milk").await;
+        let active = client.add("walk+dog").await;
+        client
+            .send(Method::POST, &format!("/todo/{done}/toggle"), "")
+            .await;
+
+        let (status, body) = client
+            .send_with(Method::POST, "/toggle-todos", "", &[("x-delta", "true")])
+            .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!(r#"class="completed" id="todo-{active}""#)));
+        assert!(!body.contains(&format!(r#"id="todo-{done}""#)));
+        assert!(body.contains(r#"id="footer""#));
+        assert!(!body.contains("<main"));
+    }
+
+    #[tokio::test]
+    async fn clear_completed_leaves_active_todos() {
+        let mut client = Client::new().await;
+        let done = client.add("buy+milk").await;
+        let active = client.add("walk+dog").await;
+        client
+            .send(Method::POST, &format!("/todo/{done}/toggle"), "")
+            .await;
+
+        let (status, body) = client.send(Method::POST, "/clear-completed", "").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body.contains(&format!(r#"id="todo-{done}""#)));
+        assert!(body.contains(&format!(r#"id="todo-{active}""#)));
+        assert!(!body.contains("Clear completed"));
+
+        let (status, body) = client.send(Method::GET, "/archive", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("buy milk"));
+        assert!(!body.contains("walk dog"));
+    }
+
+    #[tokio::test]
+    async fn undo_brings_back_cleared_todos() {
+        let mut client = Client::new().await;
+        let done = client.add("buy+milk").await;
+        client.add("walk+dog").await;
+        client
+            .send(Method::POST, &format!("/todo/{done}/toggle"), "")
+            .await;
+        client.send(Method::POST, "/clear-completed", "").await;
+
+        let (status, body) = client.send(Method::POST, "/undo", "").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(&format!(r#"class="completed" id="todo-{done}""#)));
+        let (_, body) = client.send(Method::GET, "/archive", "").await;
+        assert!(!body.contains("buy milk"));
+    }
+}
@@ -0,0 +1,37 @@
+use maud::{html, Markup, Render};
+
+use crate::{i18n::Strings, state::State};
+
+/// Tabs for switching between the session's named lists, and an input to start a new one.
+pub struct ListTabs<'a> {
+    pub state: &'a State,
+    pub strings: &'a Strings,
+    pub oob: bool,
+}
+
+impl<'a> ListTabs<'a> {
+    pub fn new(state: &'a State, strings: &'a Strings) -> Self {
+        ListTabs {
+            state,
+            strings,
+            oob: true,
+        }
+    }
+}
+
+impl Render for ListTabs<'_> {
+    fn render(&self) -> Markup {
+        html! { nav.lists #lists hx-swap-oob=[self.oob.then(|| "true")] {
+            ul {
+                @for name in self.state.list_names() { li {
+                    form hx-post="/list" hx-swap="none" {
+                        input type="hidden" name="name" value=(name);
+                        button.selected[name == self.state.active_list] { (name) }
+                    }
+                } }
+            }
+            input.new-list name="name" placeholder=(self.strings.new_list) hx-post="/list" hx-swap="none"
+                x-data "x-on:htmx:after-request"="$event.detail.successful && ($event.target.value = '')";
+        } }
+    }
+}
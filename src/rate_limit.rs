@@ -0,0 +1,107 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use tower_sessions::Session;
+
+use crate::{config::Config, state::State as TodoState};
+
+/// Buckets are only swept once there are this many, so the map stays bounded without scanning it
+/// on every request.
+const SWEEP_THRESHOLD: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// A token bucket per client. Each client may make [`Config::rate_limit_burst`] requests at once,
+/// and regains [`Config::rate_limit_per_second`] of them every second.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl RateLimiter {
+    /// Takes a token from `key`'s bucket, or returns how many seconds until one is available.
+    fn acquire(&self, key: String, config: &Config) -> Result<(), u64> {
+        let rate = f64::from(config.rate_limit_per_second.get());
+        let burst = f64::from(config.rate_limit_burst.get());
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= SWEEP_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.refilled_at).as_secs_f64() * rate < burst
+            });
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            refilled_at: now,
+        });
+        bucket.tokens = (bucket.tokens
+            + now.duration_since(bucket.refilled_at).as_secs_f64() * rate)
+            .min(burst);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / rate).ceil() as u64)
+        }
+    }
+}
+
+/// Answers mutating requests with 429 Too Many Requests once their client runs out of tokens.
+/// Clients are told apart by session, falling back to their address before they have one, or when
+/// their cookie names a session the store doesn't hold. Safe methods are never limited.
+pub async fn rate_limit(
+    State(limiter): State<RateLimiter>,
+    Extension(config): Extension<Arc<Config>>,
+    session: Session,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method().is_safe() {
+        return next.run(request).await;
+    }
+
+    let key = match verified_id(&session).await {
+        Some(id) => id,
+        None => request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string())
+            .unwrap_or_default(),
+    };
+
+    match limiter.acquire(key, &config) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, HeaderValue::from(retry_after))],
+            "too many requests, slow down",
+        )
+            .into_response(),
+    }
+}
+
+/// The id of the request's session once it has been loaded from the store. The cookie alone can
+/// name any id, so a client could otherwise get a fresh bucket with every request.
+async fn verified_id(session: &Session) -> Option<String> {
+    // Loading the session forgets its id when the store doesn't hold it.
+    session.get_value(TodoState::KEY).await.ok()?;
+    session.id().map(|id| id.to_string())
+}
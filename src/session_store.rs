@@ -0,0 +1,315 @@
+use std::time::Duration;
+
+use axum::{async_trait, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use tower_sessions::{
+    session::{self, Id, Record},
+    session_store::{self, Error},
+    ExpiredDeletion, SessionStore,
+};
+
+use crate::{
+    db::{Database, DbError},
+    state::State,
+};
+
+/// A session as kept in the database, laid out the way `tower-sessions-surrealdb-store` kept them
+/// so sessions it saved still load.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct StoredSession {
+    /// The whole record, MessagePack encoded.
+    data: Vec<u8>,
+    /// When the session expires, in seconds since the Unix epoch.
+    expiry_date: i64,
+}
+
+impl StoredSession {
+    fn encode(record: &Record) -> session_store::Result<Self> {
+        Ok(Self {
+            data: rmp_serde::to_vec(record).map_err(|err| Error::Encode(err.to_string()))?,
+            expiry_date: record.expiry_date.unix_timestamp(),
+        })
+    }
+
+    fn decode(&self) -> session_store::Result<Record> {
+        rmp_serde::from_slice(&self.data).map_err(|err| Error::Decode(err.to_string()))
+    }
+}
+
+/// Keeps sessions in `table` through a [`Database`], so a session load or save that loses the
+/// connection reconnects and retries like any other query.
+#[derive(Clone, Debug)]
+pub struct DatabaseSessionStore {
+    db: Database,
+    table: &'static str,
+}
+
+impl DatabaseSessionStore {
+    pub fn new(db: Database, table: &'static str) -> Self {
+        Self { db, table }
+    }
+
+    /// The id of every session in the store, including expired ones not yet deleted.
+    pub async fn ids(&self) -> session_store::Result<Vec<Id>> {
+        let table = self.table;
+        let ids: Vec<String> = self
+            .db
+            .run(|db| async move {
+                db.query("SELECT VALUE meta::id(id) FROM type::table($table)")
+                    .bind(("table", table))
+                    .await?
+                    .take(0)
+            })
+            .await
+            .map_err(backend)?;
+        Ok(ids.iter().filter_map(|id| id.parse().ok()).collect())
+    }
+
+    /// Permanently removes the todos each session has had in its trash for longer than `grace`.
+    /// Only sessions that had any are saved again, so a request saving the same session meanwhile
+    /// can only be written over when it races one of those.
+    pub async fn purge_trash(&self, grace: chrono::Duration) -> session_store::Result<()> {
+        for id in self.ids().await? {
+            let Some(mut record) = self.load(&id).await? else {
+                continue;
+            };
+            let Some(value) = record.data.get_mut(State::KEY) else {
+                continue;
+            };
+            let mut state =
+                State::deserialize(value.take()).map_err(|err| Error::Decode(err.to_string()))?;
+            let purged = state.purge_trash(grace);
+            *value = serde_json::to_value(&state).map_err(|err| Error::Encode(err.to_string()))?;
+            if purged {
+                self.save(&record).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs [`purge_trash`](Self::purge_trash) every `period`, so trash doesn't linger in the
+    /// sessions nobody comes back to. Stops at the first pass that fails, like
+    /// [`ExpiredDeletion::continuously_delete_expired`].
+    pub async fn continuously_purge_trash(
+        self,
+        period: Duration,
+        grace: chrono::Duration,
+    ) -> session_store::Result<()> {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            self.purge_trash(grace).await?;
+        }
+    }
+}
+
+fn backend(err: DbError) -> Error {
+    Error::Backend(err.to_string())
+}
+
+#[async_trait]
+impl ExpiredDeletion for DatabaseSessionStore {
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        let table = self.table;
+        self.db
+            .run(|db| async move {
+                db.query("DELETE type::table($table) WHERE expiry_date <= time::unix(time::now())")
+                    .bind(("table", table))
+                    .await?
+                    .check()?;
+                Ok(())
+            })
+            .await
+            .map_err(backend)
+    }
+}
+
+#[async_trait]
+impl SessionStore for DatabaseSessionStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        let table = self.table;
+        loop {
+            let id = record.id.to_string();
+            let taken: Option<StoredSession> = self
+                .db
+                .run(|db| {
+                    let id = id.clone();
+                    async move { db.select((table, id)).await }
+                })
+                .await
+                .map_err(backend)?;
+            if taken.is_none() {
+                break;
+            }
+            record.id = Id::default();
+        }
+        self.save(record).await
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let table = self.table;
+        let id = record.id.to_string();
+        let stored = StoredSession::encode(record)?;
+        let _: Option<StoredSession> = self
+            .db
+            .run(|db| {
+                let id = id.clone();
+                let stored = stored.clone();
+                async move { db.update((table, id)).content(stored).await }
+            })
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn load(&self, id: &Id) -> session_store::Result<Option<Record>> {
+        let table = self.table;
+        let id = id.to_string();
+        let stored: Option<StoredSession> = self
+            .db
+            .run(|db| {
+                let id = id.clone();
+                async move {
+                    db.query(
+                        "SELECT expiry_date, data FROM type::thing($table, $id) \
+                         WHERE expiry_date > time::unix(time::now())",
+                    )
+                    .bind(("table", table))
+                    .bind(("id", id))
+                    .await?
+                    .take(0)
+                }
+            })
+            .await
+            .map_err(backend)?;
+        stored.as_ref().map(StoredSession::decode).transpose()
+    }
+
+    async fn delete(&self, id: &Id) -> session_store::Result<()> {
+        let table = self.table;
+        let id = id.to_string();
+        let _: Option<StoredSession> = self
+            .db
+            .run(|db| {
+                let id = id.clone();
+                async move { db.delete((table, id)).await }
+            })
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+}
+
+/// A session that couldn't be loaded or saved is answered with 503 Service Unavailable, since the
+/// store is what failed, not the request. One that did load but holds something that won't decode
+/// is answered with 500.
+pub fn session_error(err: session::Error) -> (StatusCode, String) {
+    match err {
+        session::Error::Store(err) => store_error(err),
+        session::Error::SerdeJson(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+    }
+}
+
+/// See [`session_error`].
+pub fn store_error(err: Error) -> (StatusCode, String) {
+    let status = match &err {
+        Error::Backend(_) => StatusCode::SERVICE_UNAVAILABLE,
+        Error::Encode(_) | Error::Decode(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, format!("the session store failed: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+    use tower_sessions::{
+        cookie::time::{Duration, OffsetDateTime},
+        session::{self, Id, Record},
+        session_store::Error,
+        ExpiredDeletion, SessionStore,
+    };
+
+    use super::{session_error, DatabaseSessionStore};
+    use crate::{
+        config::Config,
+        db::{self, Database},
+        state::State,
+        todos::Todo,
+    };
+
+    async fn store() -> DatabaseSessionStore {
+        let config = Config::default();
+        let db = db::connect(&config).await.unwrap();
+        DatabaseSessionStore::new(Database::new(db, Arc::new(config)), "sessions")
+    }
+
+    fn record(expires_in: Duration) -> Record {
+        Record {
+            id: Id::default(),
+            data: [("key".to_string(), "value".into())].into(),
+            expiry_date: OffsetDateTime::now_utc().replace_nanosecond(0).unwrap() + expires_in,
+        }
+    }
+
+    #[tokio::test]
+    async fn sessions_round_trip_until_they_expire() {
+        let store = store().await;
+        let mut live = record(Duration::hours(1));
+        let mut expired = record(Duration::hours(-1));
+        store.create(&mut live).await.unwrap();
+        store.create(&mut expired).await.unwrap();
+
+        assert_eq!(store.load(&live.id).await.unwrap(), Some(live.clone()));
+        assert_eq!(store.load(&expired.id).await.unwrap(), None);
+
+        store.delete_expired().await.unwrap();
+        assert!(store.load(&live.id).await.unwrap().is_some());
+        store.delete(&live.id).await.unwrap();
+        assert_eq!(store.load(&live.id).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn purging_trash_removes_only_expired_todos() {
+        let store = store().await;
+        let now = chrono::Utc::now();
+        let trashed = |id, minutes_ago| Todo {
+            deleted_at: Some(now - chrono::Duration::minutes(minutes_ago)),
+            ..Todo::new(id, "chore")
+        };
+        let state = State {
+            trash: vec![trashed(1, 90), trashed(2, 5)],
+            ..State::default()
+        };
+        let value = serde_json::to_value(&state).unwrap();
+        let mut record = Record {
+            data: [(State::KEY.to_string(), value)].into(),
+            ..record(Duration::hours(1))
+        };
+        store.create(&mut record).await.unwrap();
+
+        store
+            .purge_trash(chrono::Duration::minutes(60))
+            .await
+            .unwrap();
+
+        let mut record = store.load(&record.id).await.unwrap().unwrap();
+        let state: State = serde_json::from_value(record.data.remove(State::KEY).unwrap()).unwrap();
+        let ids: Vec<u64> = state.trash.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, [2]);
+    }
+
+    #[test]
+    fn unreachable_stores_are_unavailable() {
+        let (status, _) = session_error(session::Error::Store(Error::Backend(
+            "connection lost".to_string(),
+        )));
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        let (status, _) = session_error(session::Error::Store(Error::Decode(
+            "not a record".to_string(),
+        )));
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
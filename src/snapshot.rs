@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use axum::{extract::Path, http::StatusCode, response::IntoResponse, Extension, Form, Json};
+use maud::{html, DOCTYPE};
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{config::Config, i18n::Strings, state::State, todos::ReadOnlyList, STYLESHEET_HREF};
+
+#[derive(Deserialize)]
+pub struct SnapshotForm {
+    name: String,
+}
+pub async fn create_snapshot(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<SnapshotForm>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "snapshot names may not be empty".to_string(),
+        ));
+    }
+
+    let mut state = State::read(session.clone(), &config).await?;
+    if !state.snapshots.contains_key(name) && state.snapshots.len() >= State::MAX_SNAPSHOTS {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("at most {} snapshots can be kept", State::MAX_SNAPSHOTS),
+        ));
+    }
+    state
+        .snapshots
+        .insert(name.to_string(), state.todos.clone());
+    state.write(session).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct SnapshotName {
+    name: String,
+}
+pub async fn snapshot(
+    session: Session,
+    Extension(config): Extension<Arc<Config>>,
+    Path(path): Path<SnapshotName>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+    Ok(match state.snapshots.get(&path.name) {
+        Some(todos) => Json(todos).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    })
+}
+
+pub async fn view_snapshot(
+    session: Session,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    Path(path): Path<SnapshotName>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let state = State::read(session, &config).await?;
+    let Some(todos) = state.snapshots.get(&path.name) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    Ok(html! { (DOCTYPE) html lang=(strings.lang) {
+        head {
+            meta charset="utf-8";
+            meta name="viewport" content="width=device-width, initial-scale=1.0";
+            link rel="stylesheet" href=(*STYLESHEET_HREF);
+            title { (strings.snapshot) " " (path.name) " - TodoMVC: axum, htmx, and maud" }
+        }
+        body {
+            section.todoapp {
+                header.header { h1 { (strings.title) } }
+                main.main { (ReadOnlyList(todos)) }
+                footer.footer { span.todo-count { (strings.snapshot) " " strong { (path.name) } } }
+            }
+        }
+    } }
+    .into_response())
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use axum::{http::StatusCode, Extension, Form};
+use chrono::Utc;
+use maud::{html, Markup};
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    config::Config,
+    events::{Action, Events},
+    i18n::Strings,
+    state::State,
+    todos::{List, Todo},
+    Id,
+};
+
+fn default_delimiter() -> String {
+    "\n".to_string()
+}
+#[derive(Deserialize)]
+pub struct SplitForm {
+    #[serde(default = "default_delimiter")]
+    delimiter: String,
+}
+/// Replaces a todo with one todo per non-empty part of its description. The parts keep the
+/// original's completion but not its estimate, which was for the whole, nor its subtasks. Fails
+/// with 400 Bad Request, keeping the todo, when no part has anything in it.
+pub async fn split_todo(
+    session: Session,
+    Extension(events): Extension<Events>,
+    Extension(strings): Extension<&'static Strings>,
+    Extension(config): Extension<Arc<Config>>,
+    path: Id,
+    Form(form): Form<SplitForm>,
+) -> Result<Markup, (StatusCode, String)> {
+    if form.delimiter.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "the delimiter may not be empty".to_string(),
+        ));
+    }
+
+    let mut state = State::read(session.clone(), &config).await?;
+    let Some(index) = state.todos.iter().position(|todo| todo.id == path.id) else {
+        return Err((StatusCode::NOT_FOUND, "no such todo".to_string()));
+    };
+    let original = state.todos[index].clone();
+    let parts: Vec<Todo> = original
+        .description
+        .split(form.delimiter.as_str())
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| Todo {
+            description: part.to_string(),
+            id: state.next_id(),
+            estimate_minutes: None,
+            updated_at: Utc::now(),
+            version: 0,
+            subtasks: Vec::new(),
+            ..original.clone()
+        })
+        .collect();
+    if parts.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "the todo has nothing to split into".to_string(),
+        ));
+    }
+    let max = config.max_todos;
+    if state.todos.len() - 1 + parts.len() > max {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("a list may hold at most {max} todos"),
+        ));
+    }
+
+    let added: Vec<u64> = parts.iter().map(|todo| todo.id).collect();
+    state.todos.splice(index..=index, parts);
+    state.write(session.clone()).await?;
+    events.record(&session, Action::Deleted, [path.id]).await;
+    events.record(&session, Action::Added, added).await;
+
+    Ok(html! { (List::new(&state, &config, strings)) })
+}
@@ -1,26 +1,922 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, VecDeque},
+    hash::{DefaultHasher, Hash, Hasher},
+    num::NonZeroU32,
+};
+
+use axum::http::StatusCode;
+use chrono::{Days, NaiveDate, Utc, Weekday};
 use serde::{Deserialize, Serialize};
 use tower_sessions::Session;
 
-use crate::{filter::Filter, todos::Todo};
+use crate::{
+    config::Config,
+    filter::{Filter, TagFilter},
+    session_store::session_error,
+    todos::{find_matches, CompletedStyle, Todo},
+};
+
+/// What the page does when a todo is completed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum Celebration {
+    #[default]
+    None,
+    Confetti,
+}
+
+impl Celebration {
+    /// The `HX-Trigger` value announcing a completion, if it should be celebrated.
+    pub fn trigger(self) -> Option<&'static str> {
+        match self {
+            Celebration::None => None,
+            Celebration::Confetti => Some("celebrate"),
+        }
+    }
+}
+
+/// The colors the page is shown in.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Whichever of the two the browser prefers.
+    #[default]
+    System,
+}
+
+impl Theme {
+    /// The page's `data-theme` attribute, which the stylesheet picks its colors by.
+    pub fn attr(self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::System => "system",
+        }
+    }
+}
+
+/// Where newly added todos go in the list.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum AddPosition {
+    Top,
+    #[default]
+    Bottom,
+}
+
+/// The order the list is shown in.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum SortOrder {
+    /// The order the todos were added in.
+    #[default]
+    Added,
+    /// Highest priority first, keeping the added order within each priority.
+    Priority,
+    /// Most recently completed first, then the active todos in the order they were added.
+    Completed,
+}
 
-#[derive(Default, Deserialize, Serialize)]
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
 pub struct State {
+    /// The todos of the active list. The other lists wait in `lists` until switched to, so a
+    /// state saved before there were named lists reads as its default list.
     pub todos: Vec<Todo>,
+    pub active_list: String,
+    /// Every list except the active one, by name.
+    pub lists: BTreeMap<String, Vec<Todo>>,
     pub filter: Filter,
+    /// Only todos carrying these tags are listed, when there are any.
+    pub tag_filters: TagFilter,
+    pub sort: SortOrder,
+    /// Only todos whose description contains this, ignoring case, are listed when it isn't empty.
+    pub search: String,
+    /// Focus mode, which folds completed todos away into a count under every filter.
+    pub hide_completed: bool,
+    pub completed_style: CompletedStyle,
+    pub celebration: Celebration,
+    pub theme: Theme,
+    pub add_position: AddPosition,
+    /// The first day of the week for week-based views.
+    pub week_start: Weekday,
+    /// Read-only copies of the list, by name.
+    pub snapshots: BTreeMap<String, Vec<Todo>>,
+    /// Todos set aside as templates, kept out of the list until instantiated.
+    pub templates: Vec<Todo>,
+    /// Deleted todos, restorable until they are older than the configured grace period.
+    pub trash: Vec<Todo>,
+    /// Completed todos cleared from the list, kept for looking back on.
+    pub archive: Vec<Todo>,
+    /// The todos created by the most recent requests carrying an `Idempotency-Key`, by key, oldest
+    /// first.
+    pub idempotency_keys: VecDeque<(String, u64)>,
+    /// The ids of the todos removed by the last delete or clear, which `POST /undo` brings back.
+    pub undo: Vec<u64>,
+    /// Incremented on every write, so a page rendered from an older state can be told apart.
+    pub version: u64,
+    /// The active list's todos as each of the latest writes left them, by version, oldest first,
+    /// so a conflict can show what changed since a page was rendered.
+    pub history: VecDeque<(u64, Vec<Todo>)>,
+    /// The id the next new todo or subtask is given, or 0 for a state saved before ids were
+    /// allocated per session, which then starts after the highest id it holds.
+    pub next_id: u64,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            todos: Vec::new(),
+            active_list: Self::DEFAULT_LIST.to_string(),
+            lists: BTreeMap::new(),
+            filter: Filter::default(),
+            tag_filters: TagFilter::default(),
+            sort: SortOrder::default(),
+            search: String::new(),
+            hide_completed: false,
+            completed_style: CompletedStyle::default(),
+            celebration: Celebration::default(),
+            theme: Theme::default(),
+            add_position: AddPosition::default(),
+            week_start: Weekday::Mon,
+            snapshots: BTreeMap::new(),
+            templates: Vec::new(),
+            trash: Vec::new(),
+            archive: Vec::new(),
+            idempotency_keys: VecDeque::new(),
+            undo: Vec::new(),
+            version: 0,
+            history: VecDeque::new(),
+            next_id: 0,
+        }
+    }
 }
 
 impl State {
-    const KEY: &'static str = "state";
+    pub const KEY: &'static str = "state";
+    /// How many snapshots a session may keep.
+    pub const MAX_SNAPSHOTS: usize = 20;
+    /// How many named lists a session may keep.
+    pub const MAX_LISTS: usize = 20;
+    pub const DEFAULT_LIST: &'static str = "Default";
+    /// How many idempotency keys a session remembers before forgetting the oldest.
+    pub const MAX_IDEMPOTENCY_KEYS: usize = 50;
+    /// How many versions of the list a session remembers before forgetting the oldest.
+    pub const MAX_HISTORY: usize = 10;
 
-    pub async fn read(session: Session) -> Self {
-        session
+    /// The names of all lists, the active one included, in order.
+    pub fn list_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.lists.keys().map(String::as_str).collect();
+        names.push(&self.active_list);
+        names.sort_unstable();
+        names
+    }
+
+    /// Makes `name` the active list, creating it empty if it doesn't exist yet.
+    pub fn switch_list(&mut self, name: &str) {
+        if name == self.active_list {
+            return;
+        }
+        let todos = self.lists.remove(name).unwrap_or_default();
+        // The last delete was from the list being left, so it can't be undone from the next one.
+        self.undo.clear();
+        let previous = std::mem::replace(&mut self.active_list, name.to_string());
+        self.lists
+            .insert(previous, std::mem::replace(&mut self.todos, todos));
+    }
+
+    /// Whether the list shows `todo` under the current filter, tag and search, and focus mode.
+    pub fn shows(&self, todo: &Todo) -> bool {
+        self.matches(todo) && !(self.hide_completed && todo.completed)
+    }
+
+    /// How many completed todos focus mode folds away that the list would otherwise show.
+    pub fn num_folded(&self) -> usize {
+        if !self.hide_completed {
+            return 0;
+        }
+        self.todos
+            .iter()
+            .filter(|todo| todo.completed && self.matches(todo))
+            .count()
+    }
+
+    fn matches(&self, todo: &Todo) -> bool {
+        let filtered = self.filter.matches(todo);
+        let tagged = self.tag_filters.matches(todo);
+        let searched =
+            self.search.is_empty() || !find_matches(&todo.description, &self.search).is_empty();
+
+        filtered && tagged && searched
+    }
+
+    /// The todos the list shows, in the order it shows them.
+    pub fn visible(&self) -> Vec<&Todo> {
+        let mut visible: Vec<&Todo> = self.todos.iter().filter(|todo| self.shows(todo)).collect();
+        match self.sort {
+            SortOrder::Added => {}
+            SortOrder::Priority => visible.sort_by_key(|todo| Reverse(todo.priority)),
+            SortOrder::Completed => visible.sort_by_key(|todo| Reverse(todo.completed_at)),
+        }
+        visible
+    }
+
+    pub fn num_active(&self) -> usize {
+        self.todos.iter().filter(|todo| !todo.completed).count()
+    }
+
+    pub fn num_completed(&self) -> usize {
+        self.todos.iter().filter(|todo| todo.completed).count()
+    }
+
+    /// The day all active todos are projected to be done, working through their estimates at
+    /// `capacity` minutes a day starting on `today`. Todos without an estimate are not counted.
+    pub fn eta(&self, today: NaiveDate, capacity: NonZeroU32) -> NaiveDate {
+        let remaining: u64 = self
+            .todos
+            .iter()
+            .filter(|todo| !todo.completed)
+            .filter_map(|todo| todo.estimate_minutes)
+            .map(u64::from)
+            .sum();
+        let days = remaining
+            .div_ceil(u64::from(capacity.get()))
+            .saturating_sub(1);
+
+        today + Days::new(days)
+    }
+
+    /// How many of the list's todos were completed in each week, by the day the week starts on
+    /// under [`State::week_start`].
+    pub fn completed_by_week(&self) -> BTreeMap<NaiveDate, usize> {
+        let mut weeks = BTreeMap::new();
+        for completed_at in self.todos.iter().filter_map(|todo| todo.completed_at) {
+            let week = completed_at.date_naive().week(self.week_start).first_day();
+            *weeks.entry(week).or_insert(0) += 1;
+        }
+        weeks
+    }
+
+    /// A fingerprint of what a page rendered from this state shows, for use as an `ETag`. The
+    /// version changes on every write, and the todos and filter tell apart states from different
+    /// sessions that happen to share a version.
+    pub fn etag(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.version.hash(&mut hasher);
+        self.active_list.hash(&mut hasher);
+        self.filter.hash(&mut hasher);
+        self.search.hash(&mut hasher);
+        for todo in &self.todos {
+            todo.id.hash(&mut hasher);
+            todo.version.hash(&mut hasher);
+            todo.completed.hash(&mut hasher);
+            todo.description.hash(&mut hasher);
+        }
+        format!(r#""{:x}""#, hasher.finish())
+    }
+
+    /// The active todo whose description matches `description`, ignoring case and surrounding
+    /// whitespace.
+    pub fn find_duplicate(&self, description: &str) -> Option<&Todo> {
+        let description = description.trim().to_lowercase();
+        self.todos
+            .iter()
+            .filter(|todo| !todo.completed)
+            .find(|todo| todo.description.trim().to_lowercase() == description)
+    }
+
+    /// Fails with 409 Conflict when the list already holds as many todos as the configured
+    /// maximum, so nothing more may be added.
+    pub fn check_capacity(&self, config: &Config) -> Result<(), (StatusCode, String)> {
+        let max = config.max_todos;
+        if self.todos.len() >= max {
+            Err((
+                StatusCode::CONFLICT,
+                format!("a list may hold at most {max} todos"),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The todo with `id` on the active list.
+    pub fn get(&self, id: u64) -> Option<&Todo> {
+        self.todos.iter().find(|todo| todo.id == id)
+    }
+
+    fn get_mut(&mut self, id: u64) -> Option<&mut Todo> {
+        self.todos.iter_mut().find(|todo| todo.id == id)
+    }
+
+    /// An id no todo or subtask of this session has had before.
+    pub fn next_id(&mut self) -> u64 {
+        if self.next_id == 0 {
+            self.next_id = self.max_id() + 1;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// The highest id of any todo or subtask the session holds, wherever it is kept.
+    fn max_id(&self) -> u64 {
+        std::iter::once(&self.todos)
+            .chain(self.lists.values())
+            .chain(self.snapshots.values())
+            .chain([&self.templates, &self.trash, &self.archive])
+            .flatten()
+            .flat_map(|todo| {
+                std::iter::once(todo.id).chain(todo.subtasks.iter().map(|subtask| subtask.id))
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Adds a new todo described by `description`, with a fresh id.
+    pub fn add(&mut self, description: &str) -> &Todo {
+        let id = self.next_id();
+        self.insert(Todo::new(id, description))
+    }
+
+    /// Whether the list holds at least the configured percentage of the most todos it may, so
+    /// it should warn that it is nearly full.
+    pub fn near_capacity(&self, config: &Config) -> bool {
+        self.todos.len() * 100 >= config.max_todos * usize::from(config.capacity_warning_percent)
+    }
+
+    /// Puts `todo` at the top or bottom of the list, as the session prefers.
+    pub fn insert(&mut self, todo: Todo) -> &Todo {
+        match self.add_position {
+            AddPosition::Top => {
+                self.todos.insert(0, todo);
+                &self.todos[0]
+            }
+            AddPosition::Bottom => {
+                self.todos.push(todo);
+                &self.todos[self.todos.len() - 1]
+            }
+        }
+    }
+
+    /// The id of the todo created by an earlier request carrying the idempotency key `key`.
+    pub fn created_for(&self, key: &str) -> Option<u64> {
+        self.idempotency_keys
+            .iter()
+            .find(|(seen, _)| seen == key)
+            .map(|&(_, id)| id)
+    }
+
+    /// Notes that the request carrying the idempotency key `key` created the todo with `id`.
+    pub fn remember_created(&mut self, key: String, id: u64) {
+        if self.idempotency_keys.len() >= Self::MAX_IDEMPOTENCY_KEYS {
+            self.idempotency_keys.pop_front();
+        }
+        self.idempotency_keys.push_back((key, id));
+    }
+
+    /// Puts an active copy of the todo with `id` right after it, with a fresh id.
+    pub fn duplicate(&mut self, id: u64) -> Option<&Todo> {
+        let index = self.todos.iter().position(|todo| todo.id == id)?;
+        let mut copy = Todo {
+            id: self.next_id(),
+            updated_at: Utc::now(),
+            version: 0,
+            ..self.todos[index].clone()
+        };
+        copy.set_completed(false);
+        self.todos.insert(index + 1, copy);
+        Some(&self.todos[index + 1])
+    }
+
+    /// Moves the todo with `id` to the trash, returning whether there was one.
+    pub fn remove(&mut self, id: u64) -> bool {
+        !self.soft_delete(|todo| todo.id == id).is_empty()
+    }
+
+    /// Takes the subtasks out of the todo with `id` and puts each right after it as a todo of its
+    /// own, with a fresh id and its completion kept. Returns their ids.
+    pub fn promote_subtasks(&mut self, id: u64) -> Vec<u64> {
+        let Some(index) = self.todos.iter().position(|todo| todo.id == id) else {
+            return Vec::new();
+        };
+        let subtasks = std::mem::take(&mut self.todos[index].subtasks);
+        let promoted: Vec<Todo> = subtasks
+            .into_iter()
+            .map(|subtask| {
+                let mut todo = Todo::new(self.next_id(), &subtask.description);
+                todo.set_completed(subtask.completed);
+                todo
+            })
+            .collect();
+        let ids = promoted.iter().map(|todo| todo.id).collect();
+        self.todos.splice(index + 1..index + 1, promoted);
+        ids
+    }
+
+    /// Completes the todo with `id`, or makes it active again if it already was. Completing a
+    /// recurring todo adds its next occurrence too.
+    pub fn toggle(&mut self, id: u64) -> Option<&Todo> {
+        let todo = self.get_mut(id)?;
+        todo.set_completed(!todo.completed);
+        todo.touch();
+        if todo.completed {
+            self.recur(id);
+        }
+        self.get(id)
+    }
+
+    /// Puts the next occurrence of the recurring todo with `id` right after it, due one interval
+    /// after it was, or after today if it had no due date. Returns the new todo's id, or nothing if
+    /// the todo doesn't recur.
+    pub fn recur(&mut self, id: u64) -> Option<u64> {
+        let index = self
+            .todos
+            .iter()
+            .position(|todo| todo.id == id && todo.recurrence.is_some())?;
+        let next_id = self.next_id();
+        let original = &self.todos[index];
+        let recurrence = original.recurrence?;
+        let from = original.due.unwrap_or_else(|| Utc::now().date_naive());
+        let next = Todo {
+            estimate_minutes: original.estimate_minutes,
+            priority: original.priority,
+            tags: original.tags.clone(),
+            due: Some(recurrence.next(from)),
+            recurrence: Some(recurrence),
+            ..Todo::new(next_id, &original.description)
+        };
+        self.todos.insert(index + 1, next);
+        Some(next_id)
+    }
+
+    /// Replaces the description of the todo with `id`, adding any tags in `description` to it.
+    pub fn set_description(&mut self, id: u64, description: &str) -> Option<&Todo> {
+        let todo = self.get_mut(id)?;
+        todo.edit_description(description);
+        todo.touch();
+        Some(todo)
+    }
+
+    /// Adds `tag` to every todo whose description matches the search `query`, returning the ids of
+    /// those that didn't already carry it.
+    pub fn tag_matching(&mut self, query: &str, tag: &str) -> Vec<u64> {
+        self.todos
+            .iter_mut()
+            .filter(|todo| !find_matches(&todo.description, query).is_empty())
+            .filter(|todo| !todo.tags.iter().any(|existing| existing == tag))
+            .map(|todo| {
+                todo.tags.push(tag.to_string());
+                todo.touch();
+                todo.id
+            })
+            .collect()
+    }
+
+    /// Moves every todo matching `predicate` to the trash, returning their ids.
+    pub fn soft_delete(&mut self, predicate: impl Fn(&Todo) -> bool) -> Vec<u64> {
+        let now = Utc::now();
+        let (deleted, kept) = std::mem::take(&mut self.todos)
+            .into_iter()
+            .partition::<Vec<_>, _>(predicate);
+        self.todos = kept;
+
+        let ids: Vec<u64> = deleted.iter().map(|todo| todo.id).collect();
+        if !ids.is_empty() {
+            self.undo.clone_from(&ids);
+        }
+        self.trash.extend(deleted.into_iter().map(|todo| Todo {
+            deleted_at: Some(now),
+            list: Some(self.active_list.clone()),
+            ..todo
+        }));
+        ids
+    }
+
+    /// Moves the completed todos to the archive, returning their ids.
+    pub fn clear_completed(&mut self) -> Vec<u64> {
+        let (completed, active) = std::mem::take(&mut self.todos)
+            .into_iter()
+            .partition::<Vec<_>, _>(|todo| todo.completed);
+        self.todos = active;
+
+        let ids: Vec<u64> = completed.iter().map(|todo| todo.id).collect();
+        if !ids.is_empty() {
+            self.undo.clone_from(&ids);
+        }
+        self.archive.extend(completed.into_iter().map(|todo| Todo {
+            list: Some(self.active_list.clone()),
+            ..todo
+        }));
+        ids
+    }
+
+    /// Brings back the todos removed by the last delete or clear that are still in the trash or
+    /// the archive, returning their ids. They go back at the end of the list they were taken from.
+    pub fn undo(&mut self) -> Vec<u64> {
+        std::mem::take(&mut self.undo)
+            .into_iter()
+            .filter(|&id| self.restore(id))
+            .collect()
+    }
+
+    /// Moves the todo with `id` out of the trash, or failing that the archive, and back onto the
+    /// list it was taken from, or the active list if that is unknown.
+    pub fn restore(&mut self, id: u64) -> bool {
+        let position = |todos: &[Todo]| todos.iter().position(|todo| todo.id == id);
+        let mut todo = if let Some(index) = position(&self.trash) {
+            self.trash.remove(index)
+        } else if let Some(index) = position(&self.archive) {
+            self.archive.remove(index)
+        } else {
+            return false;
+        };
+        let list = todo.list.take().unwrap_or_else(|| self.active_list.clone());
+        self.list_mut(&list).push(Todo {
+            deleted_at: None,
+            ..todo
+        });
+        true
+    }
+
+    /// The todos of the list called `name`, active or not, which is created if there is none.
+    fn list_mut(&mut self, name: &str) -> &mut Vec<Todo> {
+        if name == self.active_list {
+            &mut self.todos
+        } else {
+            self.lists.entry(name.to_string()).or_default()
+        }
+    }
+
+    /// Every list by name, the active one first.
+    fn all_lists(&self) -> impl Iterator<Item = (&str, &Vec<Todo>)> {
+        std::iter::once((self.active_list.as_str(), &self.todos)).chain(
+            self.lists
+                .iter()
+                .map(|(name, todos)| (name.as_str(), todos)),
+        )
+    }
+
+    /// Adds the todos of each of `other`'s lists to the list of the same name here, starting any
+    /// list this session doesn't have yet. The todos are given new ids from this session, which
+    /// are returned. Fails with 409 Conflict, changing nothing, when a list would end up holding
+    /// more than the configured maximum, or there would be too many lists.
+    pub fn merge(
+        &mut self,
+        other: State,
+        config: &Config,
+    ) -> Result<Vec<u64>, (StatusCode, String)> {
+        let max = config.max_todos;
+        let mut new_lists = 0;
+        for (name, todos) in other.all_lists().filter(|(_, todos)| !todos.is_empty()) {
+            let held = match self.all_lists().find(|&(held, _)| held == name) {
+                Some((_, held)) => held.len(),
+                None => {
+                    new_lists += 1;
+                    0
+                }
+            };
+            if held + todos.len() > max {
+                return Err((
+                    StatusCode::CONFLICT,
+                    format!("a list may hold at most {max} todos"),
+                ));
+            }
+        }
+        if self.lists.len() + 1 + new_lists > Self::MAX_LISTS {
+            return Err((
+                StatusCode::CONFLICT,
+                format!("at most {} lists can be kept", Self::MAX_LISTS),
+            ));
+        }
+
+        let mut merged = Vec::new();
+        let lists = std::iter::once((other.active_list, other.todos)).chain(other.lists);
+        for (name, todos) in lists {
+            for todo in todos {
+                let id = self.next_id();
+                merged.push(id);
+                self.list_mut(&name).push(Todo { id, ..todo });
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Permanently removes trashed todos deleted more than `grace` ago, returning whether there
+    /// were any.
+    pub fn purge_trash(&mut self, grace: chrono::Duration) -> bool {
+        let cutoff = Utc::now() - grace;
+        let held = self.trash.len();
+        self.trash.retain(|todo| {
+            todo.deleted_at
+                .is_some_and(|deleted_at| deleted_at > cutoff)
+        });
+        self.trash.len() < held
+    }
+
+    /// The session's state. The background pass only purges trash past its grace period every so
+    /// often, so it is purged here too, and can't be restored in between. Fails when the session
+    /// can't be loaded, rather than carrying on from an empty state the next write would save over
+    /// the real one.
+    pub async fn read(session: Session, config: &Config) -> Result<Self, (StatusCode, String)> {
+        let mut state: Self = session
             .get(Self::KEY)
             .await
-            .unwrap_or(None)
-            .unwrap_or_default()
+            .map_err(session_error)?
+            .unwrap_or_default();
+        state.purge_trash(chrono::Duration::minutes(i64::from(
+            config.soft_delete_grace_minutes,
+        )));
+        Ok(state)
+    }
+
+    /// The active list's todos as the write making `version` left them, if it is recent enough to
+    /// be remembered.
+    pub fn todos_at(&self, version: u64) -> Option<&[Todo]> {
+        self.history
+            .iter()
+            .find(|(remembered, _)| *remembered == version)
+            .map(|(_, todos)| todos.as_slice())
+    }
+
+    pub async fn write(&mut self, session: Session) -> Result<(), (StatusCode, String)> {
+        self.version += 1;
+        if self.history.len() >= Self::MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back((self.version, self.todos.clone()));
+        session
+            .insert(Self::KEY, &*self)
+            .await
+            .map_err(session_error)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroU32, sync::Arc};
+
+    use chrono::{Days, NaiveDate, Utc, Weekday};
+    use tower_sessions::{MemoryStore, Session};
+
+    use super::{AddPosition, State};
+    use crate::{config::Config, todos::Todo};
+
+    fn todo(id: u64) -> Todo {
+        Todo::new(id, &format!("todo {id}"))
+    }
+
+    #[test]
+    fn switching_lists_keeps_each_lists_todos() {
+        let mut state = State {
+            todos: vec![todo(1)],
+            ..State::default()
+        };
+
+        state.switch_list("Work");
+        assert!(state.todos.is_empty());
+        state.todos.push(todo(2));
+        assert_eq!(state.list_names(), ["Default", "Work"]);
+
+        state.switch_list(State::DEFAULT_LIST);
+        assert_eq!(state.todos[0].id, 1);
+        state.switch_list("Work");
+        assert_eq!(state.todos[0].id, 2);
+    }
+
+    #[test]
+    fn near_capacity_from_the_warning_threshold() {
+        // 90% of the default maximum of 1000.
+        let mut state = State {
+            todos: (0..899).map(todo).collect(),
+            ..State::default()
+        };
+        assert!(!state.near_capacity(&Config::default()));
+
+        state.todos.push(todo(899));
+        assert!(state.near_capacity(&Config::default()));
+    }
+
+    #[test]
+    fn idempotency_keys_are_bounded() {
+        let mut state = State::default();
+        for id in 0..=State::MAX_IDEMPOTENCY_KEYS as u64 {
+            state.remember_created(format!("key {id}"), id);
+        }
+
+        assert_eq!(state.created_for("key 0"), None);
+        assert_eq!(state.created_for("key 1"), Some(1));
+        assert_eq!(state.idempotency_keys.len(), State::MAX_IDEMPOTENCY_KEYS);
+    }
+
+    #[test]
+    fn add_puts_the_todo_where_the_session_prefers() {
+        let mut state = State {
+            todos: vec![todo(1)],
+            ..State::default()
+        };
+
+        let id = state.add("buy milk #errands").id;
+        assert_eq!(state.todos[1].id, id);
+        assert_eq!(state.todos[1].description, "buy milk");
+        assert_eq!(state.todos[1].tags, ["errands"]);
+
+        state.add_position = AddPosition::Top;
+        let id = state.add("walk dog").id;
+        assert_eq!(state.todos[0].id, id);
+    }
+
+    #[test]
+    fn ids_carry_on_from_the_highest_one_held() {
+        let mut state = State {
+            todos: vec![todo(3)],
+            trash: vec![todo(7)],
+            ..State::default()
+        };
+        assert_eq!(state.next_id(), 8);
+        assert_eq!(state.add("buy milk").id, 9);
+
+        // The next id is saved with the session, so a restart doesn't hand out used ids again.
+        let mut saved: State =
+            serde_json::from_value(serde_json::to_value(&state).unwrap()).unwrap();
+        assert_eq!(saved.next_id(), 10);
+    }
+
+    #[test]
+    fn eta_works_through_the_active_estimates_at_capacity() {
+        let estimated = |id, minutes, completed| Todo {
+            estimate_minutes: Some(minutes),
+            completed,
+            ..todo(id)
+        };
+        let mut state = State {
+            todos: vec![
+                estimated(1, 120, false),
+                estimated(2, 90, false),
+                estimated(3, 300, true),
+                todo(4),
+            ],
+            ..State::default()
+        };
+        let today = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        let capacity = NonZeroU32::new(60).unwrap();
+
+        // 210 minutes at 60 a day takes today and three more days.
+        assert_eq!(state.eta(today, capacity), today + Days::new(3));
+        state.todos.remove(1);
+        assert_eq!(state.eta(today, capacity), today + Days::new(1));
+        state.todos.remove(0);
+        assert_eq!(state.eta(today, capacity), today);
+    }
+
+    #[test]
+    fn weeks_start_on_the_chosen_day() {
+        let day = |day| NaiveDate::from_ymd_opt(2024, 4, day).unwrap();
+        let completed_on = |id, on: NaiveDate| Todo {
+            completed: true,
+            completed_at: Some(on.and_hms_opt(12, 0, 0).unwrap().and_utc()),
+            ..todo(id)
+        };
+        // 2024-04-07 is a Sunday and 2024-04-08 the Monday after it.
+        let mut state = State {
+            todos: vec![completed_on(1, day(7)), completed_on(2, day(8)), todo(3)],
+            ..State::default()
+        };
+
+        assert_eq!(state.completed_by_week(), [(day(1), 1), (day(8), 1)].into());
+        state.week_start = Weekday::Sun;
+        assert_eq!(state.completed_by_week(), [(day(7), 2)].into());
+    }
+
+    #[test]
+    fn remove_moves_the_todo_to_the_trash() {
+        let mut state = State {
+            todos: vec![todo(1), todo(2)],
+            ..State::default()
+        };
+
+        assert!(state.remove(1));
+        assert!(!state.remove(1));
+        assert!(state.get(1).is_none());
+        assert_eq!(state.trash[0].id, 1);
+        assert_eq!(state.undo, [1]);
+    }
+
+    #[tokio::test]
+    async fn trashed_todos_are_restorable_until_read_after_the_grace_period() {
+        let session = Session::new(None, Arc::new(MemoryStore::default()), None);
+        let mut state = State {
+            todos: vec![todo(1), todo(2)],
+            ..State::default()
+        };
+
+        assert!(state.remove(1));
+        assert!(state.get(1).is_none());
+        assert!(state.restore(1));
+        assert!(state.get(1).is_some());
+
+        assert!(state.remove(1));
+        state.write(session.clone()).await.unwrap();
+        assert_eq!(
+            State::read(session.clone(), &Config::default())
+                .await
+                .unwrap()
+                .trash[0]
+                .id,
+            1
+        );
+
+        let grace = Config::default().soft_delete_grace_minutes;
+        state.trash[0].deleted_at =
+            Some(Utc::now() - chrono::Duration::minutes(i64::from(grace) + 1));
+        state.write(session.clone()).await.unwrap();
+        let state = State::read(session, &Config::default()).await.unwrap();
+        assert!(state.trash.is_empty());
+        assert!(state.get(1).is_none());
+    }
+
+    #[test]
+    fn toggle_flips_completion() {
+        let mut state = State {
+            todos: vec![todo(1)],
+            ..State::default()
+        };
+
+        let toggled = state.toggle(1).unwrap();
+        assert!(toggled.completed);
+        assert!(toggled.completed_at.is_some());
+        assert_eq!(toggled.version, 1);
+        assert!(!state.toggle(1).unwrap().completed);
+        assert!(state.toggle(2).is_none());
+    }
+
+    #[test]
+    fn set_description_keeps_existing_tags() {
+        let mut state = State {
+            todos: vec![Todo {
+                tags: vec!["home".to_string()],
+                ..todo(1)
+            }],
+            ..State::default()
+        };
+
+        let edited = state.set_description(1, "mow the lawn #weekend").unwrap();
+        assert_eq!(edited.description, "mow the lawn");
+        assert_eq!(edited.tags, ["home", "weekend"]);
+        assert_eq!(edited.version, 1);
+        assert!(state.set_description(2, "nothing").is_none());
+    }
+
+    #[test]
+    fn clear_completed_archives_completed_todos() {
+        let mut state = State {
+            todos: vec![
+                todo(1),
+                Todo {
+                    completed: true,
+                    ..todo(2)
+                },
+            ],
+            ..State::default()
+        };
+
+        assert_eq!(state.clear_completed(), [2]);
+        assert_eq!(state.todos.len(), 1);
+        assert_eq!(state.archive[0].id, 2);
+        assert_eq!(state.archive[0].list.as_deref(), Some(State::DEFAULT_LIST));
+    }
+
+    #[test]
+    fn restored_todos_go_back_to_their_own_list() {
+        let mut state = State {
+            todos: vec![todo(1), todo(2)],
+            ..State::default()
+        };
+        state.remove(1);
+        state.switch_list("Work");
+
+        assert!(state.restore(1));
+        assert!(state.todos.is_empty());
+        state.switch_list(State::DEFAULT_LIST);
+        let ids: Vec<u64> = state.todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, [2, 1]);
+        assert_eq!(state.todos[1].list, None);
+    }
+
+    #[test]
+    fn merging_adds_each_list_to_the_one_of_the_same_name() {
+        let mut state = State {
+            todos: vec![todo(1)],
+            ..State::default()
+        };
+        let mut other = State {
+            todos: vec![todo(1)],
+            ..State::default()
+        };
+        other.switch_list("Work");
+        other.todos.push(todo(2));
 
-    pub async fn write(&self, session: Session) {
-        session.insert(Self::KEY, self).await.unwrap();
+        assert_eq!(state.merge(other, &Config::default()).unwrap(), [2, 3]);
+        let ids: Vec<u64> = state.todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, [1, 3]);
+        assert_eq!(state.lists["Work"][0].description, "todo 2");
     }
 }
@@ -1,7 +1,19 @@
+use std::{fmt, ops::Range};
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Days, Months, NaiveDate, Utc};
 use maud::{html, Markup, Render};
 use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
 
-use crate::{filter::Filter, footer::Footer, state::State};
+use crate::{
+    config::{Config, SubtaskPolicy},
+    filter::TagMode,
+    footer::Footer,
+    help,
+    i18n::Strings,
+    state::{AddPosition, State},
+};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub enum TodoPlaceholder {
@@ -19,50 +31,539 @@ impl Render for TodoPlaceholder {
     }
 }
 
+/// How completed todos are set apart from active ones.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum CompletedStyle {
+    #[default]
+    Strikethrough,
+    Faded,
+    HiddenLabel,
+}
+
+impl CompletedStyle {
+    fn class(self) -> &'static str {
+        match self {
+            CompletedStyle::Strikethrough => "completed-strikethrough",
+            CompletedStyle::Faded => "completed-faded",
+            CompletedStyle::HiddenLabel => "completed-hidden-label",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Priority::Low => "Low",
+            Priority::Medium => "Medium",
+            Priority::High => "High",
+        })
+    }
+}
+
+/// One step of a todo, checked off on its own.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Subtask {
+    pub id: u64,
+    pub description: String,
+    pub completed: bool,
+}
+
+/// How often a recurring todo comes back once completed.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Recurrence {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Recurrence {
+    /// The day one interval after `from`. A month after the 31st is the end of the next month.
+    pub fn next(self, from: NaiveDate) -> NaiveDate {
+        match self {
+            Recurrence::Daily => from + Days::new(1),
+            Recurrence::Weekly => from + Days::new(7),
+            Recurrence::Monthly => from + Months::new(1),
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Recurrence::Daily => "Daily",
+            Recurrence::Weekly => "Weekly",
+            Recurrence::Monthly => "Monthly",
+        })
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Todo {
     pub completed: bool,
     pub description: String,
     pub id: u64,
+    /// How long the todo is expected to take, in minutes.
+    #[serde(default)]
+    pub estimate_minutes: Option<u32>,
+    /// When the todo was added or last changed.
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub priority: Priority,
+    /// Tags pulled out of the description, without their leading `#`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Bumped on every change, so an edit made against an older copy can be refused.
+    #[serde(default)]
+    pub version: u64,
+    /// When the todo was moved to the trash, if it has been deleted.
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// The list the todo was on, while it is in the trash or the archive, so it goes back there.
+    #[serde(default)]
+    pub list: Option<String>,
+    /// When the todo was last completed, while it still is.
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Utc>>,
+    /// The day the todo is due, if it has one.
+    #[serde(default)]
+    pub due: Option<NaiveDate>,
+    /// Completing a recurring todo adds a fresh copy, due one interval later.
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    #[serde(default)]
+    pub subtasks: Vec<Subtask>,
 }
 
-impl Render for Todo {
-    fn render(&self) -> Markup {
+impl Todo {
+    /// The longest description, in characters, a todo may be given.
+    pub const MAX_DESCRIPTION_LENGTH: usize = 500;
+
+    pub fn validate_description(description: &str) -> Result<(), (StatusCode, String)> {
+        if description.chars().count() > Self::MAX_DESCRIPTION_LENGTH {
+            Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "todo descriptions may be at most {} characters long",
+                    Self::MAX_DESCRIPTION_LENGTH
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Cleans up a description before it is stored: whitespace runs, tabs and newlines included,
+    /// become single spaces, other control characters are dropped, and the rest is NFC-normalized
+    /// so the same text always compares equal. Fails if nothing is left, or too much is.
+    pub fn sanitize_description(description: &str) -> Result<String, (StatusCode, String)> {
+        let cleaned: String = description
+            .chars()
+            .filter(|c| c.is_whitespace() || !c.is_control())
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .nfc()
+            .collect();
+        if cleaned.is_empty() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "todo descriptions may not be empty".to_string(),
+            ));
+        }
+        Self::validate_description(&cleaned)?;
+        Ok(cleaned)
+    }
+
+    /// A new, active todo described by `text`, with any inline tags taken out of it.
+    pub fn new(id: u64, text: &str) -> Self {
+        let (description, tags) = Self::parse_tags(text);
+        Self {
+            completed: false,
+            description,
+            id,
+            estimate_minutes: None,
+            updated_at: Utc::now(),
+            priority: Priority::default(),
+            tags,
+            version: 0,
+            deleted_at: None,
+            list: None,
+            completed_at: None,
+            due: None,
+            recurrence: None,
+            subtasks: Vec::new(),
+        }
+    }
+
+    /// Splits inline `#tag` tokens out of `description`, returning the remaining text and the
+    /// tags in the order they first appear. A tag is made of letters, digits, `-` and `_`.
+    pub fn parse_tags(description: &str) -> (String, Vec<String>) {
+        let mut tags: Vec<String> = Vec::new();
+        let mut words = Vec::new();
+        for word in description.split_whitespace() {
+            match word.strip_prefix('#') {
+                Some(tag)
+                    if !tag.is_empty()
+                        && tag
+                            .chars()
+                            .all(|c| c.is_alphanumeric() || c == '-' || c == '_') =>
+                {
+                    let tag = tag.to_lowercase();
+                    if !tags.contains(&tag) {
+                        tags.push(tag);
+                    }
+                }
+                _ => words.push(word),
+            }
+        }
+        (words.join(" "), tags)
+    }
+
+    /// Replaces the description with `text`, adding any tags in it to the ones the todo has.
+    pub fn edit_description(&mut self, text: &str) {
+        let (description, tags) = Self::parse_tags(text);
+        self.description = description;
+        for tag in tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+    }
+
+    /// Completes the todo or makes it active again, noting when it was completed.
+    pub fn set_completed(&mut self, completed: bool) {
+        if completed && !self.completed {
+            self.completed_at = Some(Utc::now());
+        } else if !completed {
+            self.completed_at = None;
+        }
+        self.completed = completed;
+    }
+
+    /// Adds an active subtask described by `description`, with the fresh `id`.
+    pub fn add_subtask(&mut self, id: u64, description: &str) {
+        self.subtasks.push(Subtask {
+            id,
+            description: description.to_string(),
+            completed: false,
+        });
+    }
+
+    /// How many of the subtasks are completed, out of how many there are.
+    pub fn subtask_progress(&self) -> (usize, usize) {
+        let completed = self
+            .subtasks
+            .iter()
+            .filter(|subtask| subtask.completed)
+            .count();
+        (completed, self.subtasks.len())
+    }
+
+    /// Marks the todo as changed just now.
+    pub fn touch(&mut self) {
+        self.updated_at = Utc::now();
+        self.version += 1;
+    }
+
+    /// The description cut down to `limit` characters, if it is longer than that.
+    fn truncated_description(&self, limit: usize) -> Option<String> {
+        (self.description.chars().count() > limit).then(|| {
+            let mut truncated: String = self.description.chars().take(limit).collect();
+            truncated.push('…');
+            truncated
+        })
+    }
+}
+
+/// How long ago `then` was, as of `now`, in the largest whole unit, e.g. "just now", "5m ago",
+/// "3h ago" or "2d ago" in English.
+pub fn relative_time(then: DateTime<Utc>, now: DateTime<Utc>, strings: &Strings) -> String {
+    let elapsed = now - then;
+    let words = &strings.elapsed;
+    let (count, unit) = if elapsed.num_days() > 0 {
+        (elapsed.num_days(), words.days)
+    } else if elapsed.num_hours() > 0 {
+        (elapsed.num_hours(), words.hours)
+    } else if elapsed.num_minutes() > 0 {
+        (elapsed.num_minutes(), words.minutes)
+    } else {
+        return words.just_now.to_string();
+    };
+    format!("{}{count}{unit}{}", words.before, words.after)
+}
+
+/// The length in bytes of `query` at the start of `text`, when `text` starts with it ignoring case.
+fn match_len(text: &str, query: &str) -> Option<usize> {
+    let mut text_chars = text.chars();
+    let mut len = 0;
+    for q in query.chars() {
+        let t = text_chars.next()?;
+        if !t.to_lowercase().eq(q.to_lowercase()) {
+            return None;
+        }
+        len += t.len_utf8();
+    }
+    Some(len)
+}
+
+/// The byte ranges of `text` matching `query` ignoring case, without overlaps.
+pub fn find_matches(text: &str, query: &str) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+    if query.is_empty() {
+        return matches;
+    }
+    let mut start = 0;
+    while let Some(c) = text[start..].chars().next() {
+        match match_len(&text[start..], query) {
+            Some(len) => {
+                matches.push(start..start + len);
+                start += len;
+            }
+            None => start += c.len_utf8(),
+        }
+    }
+    matches
+}
+
+/// `text` with every match of `query` wrapped in `<mark>`.
+fn highlight(text: &str, query: &str) -> Markup {
+    let mut parts = Vec::new();
+    let mut end = 0;
+    for range in find_matches(text, query) {
+        parts.push((&text[end..range.start], false));
+        parts.push((&text[range.clone()], true));
+        end = range.end;
+    }
+    parts.push((&text[end..], false));
+
+    html! { @for (part, matched) in parts {
+        @if matched { mark { (part) } } @else { (part) }
+    } }
+}
+
+impl Todo {
+    pub fn render(&self, config: &Config, strings: &Strings) -> Markup {
+        self.render_matching("", config, strings)
+    }
+
+    /// The todo, with the parts of its description matching the search `query` highlighted.
+    pub fn render_matching(&self, query: &str, config: &Config, strings: &Strings) -> Markup {
+        self.render_item(query, false, config, strings)
+    }
+
+    /// The todo, swapped out-of-band into the list by its id.
+    pub fn render_oob(&self, query: &str, config: &Config, strings: &Strings) -> Markup {
+        self.render_item(query, true, config, strings)
+    }
+
+    fn render_item(&self, query: &str, oob: bool, config: &Config, strings: &Strings) -> Markup {
+        let limit = config.description_render_length;
+        let truncated = self.truncated_description(limit);
+
         html! {
-            li.completed[self.completed] #{"todo-" (self.id)}
-                x-data={ r#"{"editing":false,"description":""# (self.description) r#""}"# }
+            li.completed[self.completed] #{"todo-" (self.id)} data-priority=(self.priority)
+                hx-swap-oob=[oob.then(|| "true")]
+                x-data={ r#"{"editing":false,"expanded":false,"description":""# (self.description) r#""}"# }
                 x-bind:class=r#"editing && "editing""#
                 x-on:dblclick="editing = !editing; $nextTick(() => $refs['edit-todo-input'].focus())"
-                hx-swap="outerHTML" hx-target={"#todo-" (self.id)} {
+                hx-swap="outerHTML" hx-target={"#todo-" (self.id)}
+                hx-vals={ r#"{"version":"# (self.version) "}" } {
                     div.view x-show="!editing" {
                         input.toggle type="checkbox" checked[self.completed]
-                            hx-patch={"/todo/" (self.id)} hx-include="next input[name='completed']";
-                        label x-text="description" { (self.description) }
-                        button.destroy hx-delete={"/todo/" (self.id)} { }
+                            hx-post={"/todo/" (self.id) "/toggle"};
+                        label {
+                            @if let Some(truncated) = &truncated {
+                                span x-text={ "expanded ? description : [...description].slice(0, " (limit) ").join('') + '…'" } {
+                                    (truncated)
+                                }
+                                button.show-more type="button" x-show="!expanded" x-on:click="expanded = true" { (strings.show_more) }
+                            } @else if find_matches(&self.description, query).is_empty() {
+                                span x-text="description" { (self.description) }
+                            } @else {
+                                span { (highlight(&self.description, query)) }
+                            }
+                            @for tag in &self.tags {
+                                button.tag type="button" hx-post="/tag" hx-vals={ r#"{"tag":""# (tag) r#""}"# }
+                                    hx-target="#todo-list" hx-swap="outerHTML" { "#" (tag) }
+                            }
+                            @if let Some(due) = self.due {
+                                time.due datetime=(due) { (strings.due) " " (due) }
+                            }
+                            @if let Some(recurrence) = self.recurrence {
+                                span.recurrence title={ "Repeats " (recurrence.to_string().to_lowercase()) } { "↻" }
+                            }
+                            @if !self.subtasks.is_empty() {
+                                @let (completed, total) = self.subtask_progress();
+                                span.subtask-progress title=(strings.subtasks_completed) { (completed) "/" (total) }
+                            }
+                        }
+                        // Refetches the whole todo so the label stays current, but not while it is
+                        // being edited or the page is in the background.
+                        @let at = self.completed_at.unwrap_or(self.updated_at);
+                        time.updated-at datetime=(at.to_rfc3339())
+                            hx-get={"/todo/" (self.id)}
+                            hx-trigger="every 60s [document.visibilityState === 'visible' && !this.closest('li').classList.contains('editing')]" {
+                            @if self.completed_at.is_some() { (strings.done) " " }
+                            (relative_time(at, Utc::now(), strings))
+                        }
+                        button.duplicate type="button" title=(strings.duplicate)
+                            hx-post={"/todo/" (self.id) "/duplicate"} hx-swap="none" { }
+                        @let delete = format!("/todo/{}", self.id);
+                        @match config.subtask_delete_policy {
+                            _ if self.subtasks.is_empty() => button.destroy hx-delete=(delete) { },
+                            SubtaskPolicy::Prompt => {
+                                button.destroy "x-on:click"="$refs['confirm-delete'].showModal()" { }
+                                dialog.confirm-delete x-ref="confirm-delete" {
+                                    p { (strings.delete_subtasks_prompt) }
+                                    button type="button" hx-delete={ (delete) "?subtasks=delete" } { (strings.delete_subtasks) }
+                                    button type="button" hx-delete={ (delete) "?subtasks=promote" } { (strings.promote_subtasks) }
+                                    button type="button" "x-on:click"="$el.closest('dialog').close()" { (strings.cancel) }
+                                }
+                            },
+                            SubtaskPolicy::Block => button.destroy disabled title=(strings.delete_subtasks_first) { },
+                            SubtaskPolicy::Delete | SubtaskPolicy::Promote => button.destroy hx-delete=(delete) { },
+                        }
+                        span.priority-marker title={ (self.priority) " priority" } { }
                     }
-                    input type="hidden" name="completed" value=(self.completed);
 
+                    @if !self.subtasks.is_empty() {
+                        ul.subtasks x-show="!editing" {
+                            @for subtask in &self.subtasks {
+                                @let url = format!("/todo/{}/subtask/{}", self.id, subtask.id);
+                                li.completed[subtask.completed] {
+                                    input.toggle type="checkbox" checked[subtask.completed]
+                                        hx-patch=(url) hx-vals={ r#"{"completed":"# (!subtask.completed) "}" };
+                                    label { (subtask.description) }
+                                    button.destroy hx-delete=(url) { }
+                                }
+                            }
+                        }
+                    }
+
+                    // Enter commits the edit. Escape throws it away by fetching the todo as it was
+                    // saved, which also leaves editing since the fresh copy starts out closed.
                     template x-if="editing" { div.input-container {
                         input.edit #edit-todo-input x-ref="edit-todo-input"
-                            hx-patch={"/todo/" (self.id)} name="desc" x-model="description";
-                        label.visually-hidden for="edit-todo-input" { "Edit Todo Input" }
+                            hx-patch={"/todo/" (self.id)} hx-trigger=(help::SAVE.trigger())
+                            "x-on:keydown.escape"={ "htmx.ajax('GET', '/todo/" (self.id) "', { target: '#todo-" (self.id) "', swap: 'outerHTML' })" }
+                            aria-keyshortcuts="Enter Escape"
+                            name="desc" x-model="description";
+                        label.visually-hidden for="edit-todo-input" { (strings.edit_todo_input) }
+                        input.new-subtask name="description" placeholder=(strings.add_a_subtask)
+                            hx-post={"/todo/" (self.id) "/subtask"} hx-trigger=(help::SAVE.trigger());
                     } }
                 }
         }
     }
 }
 
+/// Just the parts of the list a change touched: each changed todo swapped out-of-band, or deleted
+/// when the list no longer shows it, each removed todo deleted, and the footer.
+pub struct Delta<'a> {
+    pub state: &'a State,
+    pub changed: Vec<&'a Todo>,
+    pub removed: Vec<u64>,
+    pub config: &'a Config,
+    pub strings: &'a Strings,
+}
+
+impl Render for Delta<'_> {
+    fn render(&self) -> Markup {
+        let removed = self
+            .changed
+            .iter()
+            .filter(|todo| !self.state.shows(todo))
+            .map(|todo| todo.id)
+            .chain(self.removed.iter().copied());
+
+        html! {
+            @for todo in &self.changed {
+                @if self.state.shows(todo) { (todo.render_oob(&self.state.search, self.config, self.strings)) }
+            }
+            @for id in removed { li #{"todo-" (id)} hx-swap-oob="delete" { } }
+            (Footer::new(self.state, self.config, self.strings))
+        }
+    }
+}
+
+/// Todos rendered for reading only, without any of the controls to change them.
+pub struct ReadOnlyList<'a>(pub &'a [Todo]);
+
+impl Render for ReadOnlyList<'_> {
+    fn render(&self) -> Markup {
+        html! { ul.todo-list.read-only {
+            @for todo in self.0 {
+                li.completed[todo.completed] { div.view { label {
+                    (todo.description)
+                    @for tag in &todo.tags { span.tag { "#" (tag) } }
+                } } }
+            }
+        } }
+    }
+}
+
+/// Fetches the page of the list starting at `offset` in its own place.
+pub struct LoadMore<'a> {
+    pub offset: usize,
+    pub strings: &'a Strings,
+}
+
+impl Render for LoadMore<'_> {
+    fn render(&self) -> Markup {
+        html! {
+            li.load-more hx-get={"/todos/page?offset=" (self.offset)} hx-target="this" hx-swap="outerHTML" {
+                button type="button" { (self.strings.load_more) }
+            }
+        }
+    }
+}
+
 pub struct List<'a> {
     pub state: &'a State,
+    pub config: &'a Config,
+    pub strings: &'a Strings,
     pub oob: bool,
 }
 
-impl<'a, 'b> From<&'a State> for List<'b>
-where
-    'a: 'b,
-{
-    fn from(state: &'a State) -> Self {
-        List { state, oob: true }
+impl<'a> List<'a> {
+    pub fn new(state: &'a State, config: &'a Config, strings: &'a Strings) -> Self {
+        List {
+            state,
+            config,
+            strings,
+            oob: true,
+        }
+    }
+
+    /// What to send for `new_todo` having just been added, given the placeholder the page sent.
+    /// The Extend placeholder sits at the end of the list, so a todo added at the top is delivered
+    /// by swapping the whole list out-of-band instead.
+    pub fn added_fragment(&self, new_todo: &Todo, placeholder: TodoPlaceholder) -> Markup {
+        match (placeholder, self.state.add_position) {
+            (TodoPlaceholder::FullPayload, _) => List {
+                oob: false,
+                ..*self
+            }
+            .render(),
+            (TodoPlaceholder::Extend, AddPosition::Top) => List { oob: true, ..*self }.render(),
+            (TodoPlaceholder::Extend, AddPosition::Bottom) => self.extend_fragment(new_todo),
+        }
+    }
+
+    /// `new_todo` to go in place of the Extend placeholder at the end of the list, followed by
+    /// the updated footer and a fresh placeholder for the next todo.
+    pub fn extend_fragment(&self, new_todo: &Todo) -> Markup {
+        html! { (new_todo.render(self.config, self.strings)) (Footer::new(self.state, self.config, self.strings)) (TodoPlaceholder::Extend) }
     }
 }
 
@@ -71,38 +572,493 @@ impl Render for List<'_> {
         if self.state.todos.is_empty() {
             html! { (TodoPlaceholder::FullPayload) }
         } else {
-            let completed = self
-                .state
-                .todos
-                .iter()
-                .filter(|todo| todo.completed)
-                .count();
-            let filtered_todos: Vec<&Todo> = self
-                .state
-                .todos
-                .iter()
-                .filter(|todo| match self.state.filter {
-                    Filter::All => true,
-                    Filter::Active => !todo.completed,
-                    Filter::Completed => todo.completed,
-                })
-                .collect();
+            let (config, strings) = (self.config, self.strings);
+            let completed = self.state.num_completed();
+            let page_size = config.page_size.get();
+            let visible = self.state.visible();
 
             html! { main.main #todo-list hx-swap-oob=[self.oob.then(|| "true")] {
                 div.toggle-all-container {
                     input.toggle-all #toggle-all type="checkbox" checked=(completed)
                         hx-post="/toggle-todos";
-                    label for="toggle-all" { "Mark all as complete" }
+                    label for="toggle-all" { (strings.mark_all_complete) }
+                }
+
+                @let tag_filters = &self.state.tag_filters;
+                @if !tag_filters.tags.is_empty() {
+                    div.tag-filter {
+                        (strings.tagged)
+                        @if tag_filters.tags.len() > 1 {
+                            " " @match tag_filters.mode {
+                                TagMode::And => (strings.all_of),
+                                TagMode::Or => (strings.any_of),
+                            }
+                        }
+                        @for tag in &tag_filters.tags { " " span.tag { "#" (tag) } }
+                        button.clear-tag hx-post="/tag" hx-target="#todo-list" hx-swap="outerHTML"
+                            aria-label=(strings.show_all_tags) { "×" }
+                    }
                 }
 
-                ul.todo-list {
-                    @for todo in filtered_todos{ (todo) }
+                ul.todo-list.(self.state.completed_style.class()) {
+                    @for todo in visible.iter().take(page_size) {
+                        (todo.render_matching(&self.state.search, config, strings))
+                    }
+                    @if visible.len() > page_size { (LoadMore { offset: page_size, strings }) }
 
                     (TodoPlaceholder::Extend)
                 }
 
-                (Footer { oob: false, ..Footer::from(self.state) })
+                @let folded = self.state.num_folded();
+                @if folded > 0 {
+                    div.folded-completed {
+                        (folded) " "
+                        @if folded == 1 { (strings.item_completed) } @else { (strings.items_completed) }
+                        " "
+                        button type="button" hx-post="/focus" hx-target="#todo-list" hx-swap="outerHTML" {
+                            "(" (strings.show) ")"
+                        }
+                    }
+                }
+
+                (Footer { oob: false, ..Footer::new(self.state, config, strings) })
             } }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, Utc};
+    use maud::Render;
+
+    use super::{
+        find_matches, highlight, relative_time, CompletedStyle, List, Priority, Recurrence, Todo,
+        TodoPlaceholder,
+    };
+    use crate::{
+        config::Config,
+        filter::Filter,
+        i18n::ENGLISH,
+        state::{AddPosition, SortOrder, State},
+    };
+
+    fn todo(id: u64, completed: bool, description: &str) -> Todo {
+        Todo {
+            completed,
+            ..Todo::new(id, description)
+        }
+    }
+
+    /// Snapshots the rendered `markup` as `name`, with the timestamps that change from run to run
+    /// masked.
+    fn assert_html_snapshot(name: &str, markup: impl Render) {
+        insta::with_settings!({ filters => vec![(r#"datetime="[^"]+""#, r#"datetime="[datetime]""#)] }, {
+            insta::assert_snapshot!(name, markup.render().into_string());
+        });
+    }
+
+    fn mixed(filter: Filter) -> State {
+        State {
+            todos: vec![todo(1, false, "buy milk"), todo(2, true, "walk dog")],
+            filter,
+            ..State::default()
+        }
+    }
+
+    #[test]
+    fn tags_are_split_out_of_the_description() {
+        assert_eq!(
+            Todo::parse_tags("buy milk #shopping"),
+            ("buy milk".to_string(), vec!["shopping".to_string()])
+        );
+        assert_eq!(
+            Todo::parse_tags("#Work call #bob #work about #"),
+            (
+                "call about #".to_string(),
+                vec!["work".to_string(), "bob".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn sanitizing_collapses_whitespace_and_drops_control_characters() {
+        assert_eq!(
+            Todo::sanitize_description("\tbuy\t\tmilk\n and\u{0}  eggs\r\n").unwrap(),
+            "buy milk and eggs"
+        );
+        assert!(Todo::sanitize_description(" \t\n\u{7}").is_err());
+    }
+
+    #[test]
+    fn sanitizing_composes_accents() {
+        let decomposed = "cre\u{300}me bru\u{302}le\u{301}e";
+        assert_eq!(
+            Todo::sanitize_description(decomposed).unwrap(),
+            "cr\u{e8}me br\u{fb}l\u{e9}e"
+        );
+    }
+
+    #[test]
+    fn full_payload_placeholder_gets_the_whole_list_in_place() {
+        let state = mixed(Filter::All);
+        let html = List::new(&state, &Config::default(), &ENGLISH)
+            .added_fragment(&state.todos[1], TodoPlaceholder::FullPayload)
+            .into_string();
+
+        assert!(html.starts_with(r#"<main class="main" id="todo-list">"#));
+        assert!(html.contains("buy milk"));
+        assert!(html.contains("walk dog"));
+    }
+
+    #[test]
+    fn extend_placeholder_gets_just_the_new_todo() {
+        let state = mixed(Filter::All);
+        let html = List::new(&state, &Config::default(), &ENGLISH)
+            .added_fragment(&state.todos[1], TodoPlaceholder::Extend)
+            .into_string();
+
+        assert!(html.starts_with(r#"<li class="completed" id="todo-2""#));
+        assert!(!html.contains("buy milk"));
+        assert!(html.contains(r#"id="footer" hx-swap-oob="true""#));
+        assert!(html.ends_with(r#"<input type="hidden" name="next-todo" value="Extend">"#));
+
+        let state = State {
+            add_position: AddPosition::Top,
+            ..mixed(Filter::All)
+        };
+        let html = List::new(&state, &Config::default(), &ENGLISH)
+            .added_fragment(&state.todos[1], TodoPlaceholder::Extend)
+            .into_string();
+        assert!(html.contains(r#"hx-swap-oob="true""#));
+        assert!(html.contains("buy milk"));
+    }
+
+    #[test]
+    fn recurrences_advance_by_their_interval() {
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(Recurrence::Daily.next(day(2024, 2, 28)), day(2024, 2, 29));
+        assert_eq!(Recurrence::Weekly.next(day(2024, 12, 28)), day(2025, 1, 4));
+        assert_eq!(Recurrence::Monthly.next(day(2024, 1, 31)), day(2024, 2, 29));
+    }
+
+    #[test]
+    fn matches_ignore_case() {
+        assert_eq!(find_matches("Milk, more MILK", "milk"), vec![0..4, 11..15]);
+        assert_eq!(find_matches("crème brûlée", "BRÛ"), vec![7..11]);
+        assert!(find_matches("buy milk", "").is_empty());
+    }
+
+    #[test]
+    fn highlight_escapes_around_marks() {
+        assert_eq!(
+            highlight("<b>milk</b> & Milk", "milk").into_string(),
+            "&lt;b&gt;<mark>milk</mark>&lt;/b&gt; &amp; <mark>Milk</mark>"
+        );
+    }
+
+    #[test]
+    fn placeholder_extends_in_place_or_swaps_out_of_band() {
+        let extend = TodoPlaceholder::Extend.render().into_string();
+        assert_eq!(
+            extend,
+            r#"<input type="hidden" name="next-todo" value="Extend">"#
+        );
+
+        let full = TodoPlaceholder::FullPayload.render().into_string();
+        assert_eq!(
+            full,
+            r#"<input id="todo-list" hx-swap-oob="true" type="hidden" name="next-todo" value="FullPayload">"#
+        );
+    }
+
+    #[test]
+    fn active_todo_patches_itself() {
+        let html = todo(7, false, "buy milk")
+            .render(&Config::default(), &ENGLISH)
+            .into_string();
+
+        assert!(html.contains(r#"id="todo-7""#));
+        assert!(!html.contains(r#"class="completed""#));
+        assert!(html.contains(r##"hx-target="#todo-7""##));
+        assert!(html.contains(r#"hx-swap="outerHTML""#));
+        assert!(html.contains(r#"hx-post="/todo/7/toggle""#));
+        assert!(html.contains(r#"hx-patch="/todo/7" hx-trigger="keyup[key=='Enter']""#));
+        assert!(html.contains(r#"htmx.ajax('GET', '/todo/7'"#));
+        assert!(html.contains(r#"hx-delete="/todo/7""#));
+        assert!(html.contains(r#"hx-vals="{&quot;version&quot;:0}""#));
+        assert!(!html.contains("checked"));
+    }
+
+    #[test]
+    fn long_descriptions_are_truncated_with_a_way_to_expand_them() {
+        let limit = Config::default().description_render_length;
+        let html = todo(1, false, &"a".repeat(limit + 10))
+            .render(&Config::default(), &ENGLISH)
+            .into_string();
+        assert!(html.contains(&format!("{}…", "a".repeat(limit))));
+        assert!(html.contains(r#"class="show-more""#));
+        assert!(html.contains(r#"x-on:click="expanded = true""#));
+
+        let html = todo(2, false, "buy milk")
+            .render(&Config::default(), &ENGLISH)
+            .into_string();
+        assert!(!html.contains("show-more"));
+    }
+
+    #[test]
+    fn completed_todo_is_checked() {
+        let html = todo(8, true, "walk dog")
+            .render(&Config::default(), &ENGLISH)
+            .into_string();
+
+        assert!(html.starts_with(r#"<li class="completed" id="todo-8""#));
+        assert!(html.contains("checked"));
+    }
+
+    #[test]
+    fn completing_notes_when() {
+        let mut todo = todo(1, false, "buy milk");
+        todo.set_completed(true);
+        let completed_at = todo.completed_at.unwrap();
+
+        todo.set_completed(true);
+        assert_eq!(todo.completed_at, Some(completed_at));
+        assert!(todo
+            .render(&Config::default(), &ENGLISH)
+            .into_string()
+            .contains("done just now"));
+
+        todo.set_completed(false);
+        assert!(!todo.completed);
+        assert_eq!(todo.completed_at, None);
+    }
+
+    #[test]
+    fn relative_time_uses_the_largest_unit() {
+        let now = Utc::now();
+        assert_eq!(
+            relative_time(now - Duration::seconds(30), now, &ENGLISH),
+            "just now"
+        );
+        assert_eq!(
+            relative_time(now - Duration::minutes(2), now, &ENGLISH),
+            "2m ago"
+        );
+        assert_eq!(
+            relative_time(now - Duration::minutes(150), now, &ENGLISH),
+            "2h ago"
+        );
+        assert_eq!(
+            relative_time(now - Duration::days(3), now, &ENGLISH),
+            "3d ago"
+        );
+    }
+
+    #[test]
+    fn priority_is_marked() {
+        let html = Todo {
+            priority: Priority::High,
+            ..todo(9, false, "file taxes")
+        }
+        .render(&Config::default(), &ENGLISH)
+        .into_string();
+
+        assert!(html.contains(r#"data-priority="High""#));
+        assert!(html.contains(r#"class="priority-marker" title="High priority""#));
+    }
+
+    #[test]
+    fn priority_sort_puts_high_before_low() {
+        let state = State {
+            todos: vec![
+                Todo {
+                    priority: Priority::Low,
+                    ..todo(1, false, "water plants")
+                },
+                todo(2, false, "buy milk"),
+                Todo {
+                    priority: Priority::High,
+                    ..todo(3, false, "file taxes")
+                },
+            ],
+            sort: SortOrder::Priority,
+            ..State::default()
+        };
+        let html = List::new(&state, &Config::default(), &ENGLISH)
+            .render()
+            .into_string();
+
+        let position = |id: u64| html.find(&format!(r#"id="todo-{id}""#)).unwrap();
+        assert!(position(3) < position(2));
+        assert!(position(2) < position(1));
+    }
+
+    #[test]
+    fn completed_sort_puts_the_latest_first() {
+        let now = Utc::now();
+        let state = State {
+            todos: vec![
+                todo(1, false, "water plants"),
+                Todo {
+                    completed_at: Some(now - Duration::hours(1)),
+                    ..todo(2, true, "buy milk")
+                },
+                Todo {
+                    completed_at: Some(now),
+                    ..todo(3, true, "file taxes")
+                },
+            ],
+            sort: SortOrder::Completed,
+            ..State::default()
+        };
+        let html = List::new(&state, &Config::default(), &ENGLISH)
+            .render()
+            .into_string();
+
+        let position = |id: u64| html.find(&format!(r#"id="todo-{id}""#)).unwrap();
+        assert!(position(3) < position(2));
+        assert!(position(2) < position(1));
+    }
+
+    #[test]
+    fn focus_mode_folds_completed_todos() {
+        let state = State {
+            todos: vec![
+                todo(1, false, "water plants"),
+                todo(2, true, "buy milk"),
+                todo(3, true, "file taxes"),
+            ],
+            hide_completed: true,
+            ..State::default()
+        };
+        let html = List::new(&state, &Config::default(), &ENGLISH)
+            .render()
+            .into_string();
+
+        assert!(html.contains(r#"id="todo-1""#));
+        assert!(!html.contains(r#"id="todo-2""#));
+        assert!(!html.contains(r#"id="todo-3""#));
+        assert!(html.contains("2 items completed"));
+        assert!(html.contains(r#"hx-post="/focus""#));
+    }
+
+    #[test]
+    fn list_renders_the_first_page() {
+        let state = State {
+            todos: (1..=120).map(|id| todo(id, false, "chore")).collect(),
+            ..State::default()
+        };
+        let html = List::new(&state, &Config::default(), &ENGLISH)
+            .render()
+            .into_string();
+
+        assert_eq!(html.matches("data-priority=").count(), 50);
+        assert!(html.contains(r#"hx-get="/todos/page?offset=50""#));
+        assert!(html.contains("<strong>120</strong> items left"));
+    }
+
+    #[test]
+    fn list_is_styled_by_the_chosen_completed_style() {
+        let state = State {
+            completed_style: CompletedStyle::Faded,
+            ..mixed(Filter::All)
+        };
+        let html = List::new(&state, &Config::default(), &ENGLISH)
+            .render()
+            .into_string();
+
+        assert!(html.contains(r#"<ul class="todo-list completed-faded">"#));
+        assert!(html.contains(r#"<li class="completed" id="todo-2""#));
+    }
+
+    #[test]
+    fn empty_list_is_the_placeholder() {
+        let state = State::default();
+
+        assert_eq!(
+            List::new(&state, &Config::default(), &ENGLISH)
+                .render()
+                .into_string(),
+            TodoPlaceholder::FullPayload.render().into_string()
+        );
+    }
+
+    #[test]
+    fn list_swaps_out_of_band_unless_told_otherwise() {
+        let state = mixed(Filter::All);
+
+        let oob = List::new(&state, &Config::default(), &ENGLISH)
+            .render()
+            .into_string();
+        assert!(oob.starts_with(r#"<main class="main" id="todo-list" hx-swap-oob="true">"#));
+
+        let in_place = List {
+            oob: false,
+            ..List::new(&state, &Config::default(), &ENGLISH)
+        }
+        .render()
+        .into_string();
+        assert!(in_place.starts_with(r#"<main class="main" id="todo-list">"#));
+    }
+
+    #[test]
+    fn list_shows_the_filtered_todos() {
+        for (filter, shown, hidden) in [
+            (Filter::All, &[1, 2][..], &[][..]),
+            (Filter::Active, &[1], &[2]),
+            (Filter::Completed, &[2], &[1]),
+        ] {
+            let state = mixed(filter.clone());
+            let html = List::new(&state, &Config::default(), &ENGLISH)
+                .render()
+                .into_string();
+
+            for id in shown {
+                assert!(
+                    html.contains(&format!(r#"id="todo-{id}""#)),
+                    "{filter}: {id}"
+                );
+            }
+            for id in hidden {
+                assert!(
+                    !html.contains(&format!(r#"id="todo-{id}""#)),
+                    "{filter}: {id}"
+                );
+            }
+            assert!(html.contains(r#"value="Extend""#));
+            assert!(html.contains(r#"<footer class="footer" id="footer""#));
+        }
+    }
+
+    #[test]
+    fn todos_render_the_same() {
+        assert_html_snapshot(
+            "todo_active",
+            todo(1, false, "buy milk").render(&Config::default(), &ENGLISH),
+        );
+        assert_html_snapshot(
+            "todo_completed",
+            todo(2, true, "walk dog").render(&Config::default(), &ENGLISH),
+        );
+    }
+
+    #[test]
+    fn placeholders_render_the_same() {
+        assert_html_snapshot("placeholder_extend", TodoPlaceholder::Extend);
+        assert_html_snapshot("placeholder_full_payload", TodoPlaceholder::FullPayload);
+    }
+
+    #[test]
+    fn lists_render_the_same() {
+        assert_html_snapshot(
+            "list_empty",
+            List::new(&State::default(), &Config::default(), &ENGLISH),
+        );
+        for filter in [Filter::All, Filter::Active, Filter::Completed] {
+            let state = mixed(filter.clone());
+            let name = format!("list_mixed_{filter}").to_lowercase();
+            assert_html_snapshot(&name, List::new(&state, &Config::default(), &ENGLISH));
+        }
+    }
+}